@@ -1,13 +1,21 @@
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use chrono::TimeDelta;
+use clap::{Args, Parser, Subcommand};
+
+use crate::dev::parse_port;
+use crate::manifest::PortConfig;
+use crate::query::{Query, SortKey, parse_query};
+use crate::ttl::parse_ttl;
 
 #[derive(Parser)]
 #[command(name = "vuln-pkg")]
 #[command(
     author,
     version,
-    about = "A package manager for deliberately-vulnerable applications"
+    about = "A package manager for deliberately-vulnerable applications",
+    after_long_help = crate::error::exit_codes_help()
 )]
 pub struct Cli {
     /// Output in JSON format for automation
@@ -39,6 +47,10 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub https: bool,
 
+    /// Update the vuln-pkg block in the hosts file after `run` and `remove`
+    #[arg(long, global = true)]
+    pub sync_hosts: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -46,12 +58,46 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// List available vulnerable applications
-    List,
+    List {
+        /// Only list apps matching a query (same syntax as `search`)
+        #[arg(long, value_parser = parse_query)]
+        filter: Option<Query>,
+
+        /// Result ordering
+        #[arg(long, value_enum, default_value_t)]
+        sort: SortKey,
+    },
 
     /// Search for applications by name, description, or tags
     Search {
-        /// Search query (matches against name, description, and tags)
-        query: String,
+        /// Search terms plus optional filters: tag:<tag>, type:<prebuilt|dockerfile|git|local>,
+        /// protocol:<http|tcp|udp>, installed:<true|false>, running:<true|false>, cve:<id or year>
+        #[arg(value_parser = parse_query)]
+        query: Query,
+
+        /// Result ordering
+        #[arg(long, value_enum, default_value_t)]
+        sort: SortKey,
+    },
+
+    /// Show everything about an application: definition, build metadata and endpoints
+    Info {
+        /// Name of the application
+        app: String,
+    },
+
+    /// Open a running application in the browser (or print how to connect to TCP/UDP ports)
+    Open {
+        /// Name of the application
+        app: String,
+
+        /// Port to open, by label (e.g. "Admin") or container port; defaults to the first HTTP port
+        #[arg(long)]
+        port: Option<String>,
+
+        /// Print the URL instead of launching a browser
+        #[arg(long)]
+        print: bool,
     },
 
     /// Install a vulnerable application (pull image, create config)
@@ -60,37 +106,187 @@ pub enum Commands {
         app: String,
     },
 
-    /// Run a vulnerable application (start container with Traefik routing)
+    /// Run vulnerable applications (start containers with Traefik routing)
     Run {
-        /// Name of the application to run
-        app: String,
+        /// Names of the applications to run (glob patterns such as 'web*' allowed)
+        #[arg(required_unless_present_any = ["tag", "all"])]
+        apps: Vec<String>,
+
+        #[command(flatten)]
+        bulk: BulkArgs,
+
+        /// Remove the app(s) after this long, e.g. 90m, 2h or 1h30m (enforced by `vuln-pkg gc`)
+        #[arg(long, value_parser = parse_ttl)]
+        ttl: Option<TimeDelta>,
     },
 
-    /// Stop a running vulnerable application
+    /// Stop running vulnerable applications
     Stop {
-        /// Name of the application to stop
-        app: String,
+        /// Names of the applications to stop (glob patterns such as 'web*' allowed)
+        #[arg(required_unless_present_any = ["tag", "all"])]
+        apps: Vec<String>,
+
+        #[command(flatten)]
+        bulk: BulkArgs,
     },
 
-    /// Stop and remove a vulnerable application
+    /// Stop and remove vulnerable applications
     Remove {
-        /// Name of the application to remove
-        app: String,
+        /// Names of the applications to remove (glob patterns such as 'web*' allowed)
+        #[arg(required_unless_present_any = ["tag", "all"])]
+        apps: Vec<String>,
 
         /// Also remove the Docker image
         #[arg(long)]
         purge: bool,
+
+        #[command(flatten)]
+        bulk: BulkArgs,
     },
 
     /// Rebuild a custom application (dockerfile or git type)
     Rebuild {
         /// Name of the application to rebuild
         app: String,
+
+        /// Build without using cached layers
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Set or override a build argument (repeatable)
+        #[arg(long = "build-arg", value_name = "KEY=VALUE", value_parser = parse_build_arg)]
+        build_args: Vec<(String, String)>,
     },
 
+    /// Build an app from a working directory, run it, and rebuild it whenever its files change
+    Dev {
+        /// Directory containing the app's Dockerfile
+        path: PathBuf,
+
+        /// App name, used in its hostname (default: the directory name)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Container port to expose: 8080 (HTTP), 3306/tcp or 53/udp (repeatable; default: the Dockerfile's EXPOSE ports, else 80)
+        #[arg(long = "port", value_name = "PORT[/PROTO]", value_parser = parse_port)]
+        ports: Vec<PortConfig>,
+
+        /// Dockerfile path, relative to the directory
+        #[arg(long, default_value = "Dockerfile")]
+        dockerfile: String,
+
+        /// Set a build argument (repeatable)
+        #[arg(long = "build-arg", value_name = "KEY=VALUE", value_parser = parse_build_arg)]
+        build_args: Vec<(String, String)>,
+
+        /// Leave the app running when dev exits
+        #[arg(long)]
+        keep: bool,
+    },
+
+    /// List installed apps whose git ref or registry image has changed since they were built or pulled
+    Outdated,
+
     /// Show status of running applications
     Status,
 
+    /// Remove apps whose --ttl has expired
+    Gc {
+        /// Show which apps have expired without removing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Keep running and collect expired apps periodically
+        #[arg(long, conflicts_with = "dry_run")]
+        daemon: bool,
+
+        /// How often the daemon checks for expired apps
+        #[arg(long, value_parser = parse_ttl, default_value = "1m", requires = "daemon")]
+        interval: TimeDelta,
+    },
+
+    /// Install and start every app in a lab definition file
+    Up {
+        /// Path to the lab file (YAML)
+        lab: PathBuf,
+
+        /// Remove the lab's apps after this long, e.g. 2h (enforced by `vuln-pkg gc`)
+        #[arg(long, value_parser = parse_ttl)]
+        ttl: Option<TimeDelta>,
+    },
+
+    /// Stop and remove every app in a lab definition file
+    Down {
+        /// Path to the lab file (YAML)
+        lab: PathBuf,
+
+        /// Also remove the Docker images
+        #[arg(long)]
+        purge: bool,
+    },
+
+    /// Manage /etc/hosts entries for app hostnames (useful with --domain)
+    Hosts {
+        #[command(subcommand)]
+        command: HostsCommands,
+    },
+
+    /// Run a DNS server that answers *.<domain> with --resolve-address (for offline labs)
+    Dns {
+        /// Address to listen on (use 0.0.0.0:53 to serve other machines)
+        #[arg(long, default_value = "127.0.0.1:53")]
+        listen: SocketAddr,
+
+        /// Upstream DNS server for names outside the lab domain
+        #[arg(long, default_value = "1.1.1.1:53")]
+        upstream: SocketAddr,
+
+        /// Refuse names outside the lab domain instead of forwarding them
+        #[arg(long)]
+        no_forward: bool,
+    },
+
+    /// Serve a local HTTP+JSON API for list/search/install/run/stop/remove/status
+    Serve {
+        /// Address to listen on (requests must send `Authorization: Bearer <token>`)
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: SocketAddr,
+
+        /// Listen on a unix socket instead of TCP (no token; access via file permissions)
+        #[arg(long, conflicts_with_all = ["listen", "token"])]
+        socket: Option<PathBuf>,
+
+        /// API token (default: $VULN_PKG_API_TOKEN, or a random token printed at startup)
+        #[arg(long)]
+        token: Option<String>,
+    },
+
+    /// Diagnose common environment problems (Docker, ports, DNS, Traefik, state)
+    Doctor {
+        /// Repair what can be repaired automatically (stale state, leftover Traefik container)
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Manage the shared Traefik reverse proxy
+    Proxy {
+        #[command(subcommand)]
+        command: ProxyCommands,
+    },
+
+    /// Manage vuln-pkg's local caches
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Re-import orphaned vuln-pkg containers and prune stale state entries
+    Adopt {
+        /// Show what would change without updating state
+        #[arg(long)]
+        dry_run: bool,
+    },
+
     /// Manage manifests (show info, forget accepted manifests)
     Manifest {
         #[command(subcommand)]
@@ -98,6 +294,22 @@ pub enum Commands {
     },
 }
 
+/// Selectors and parallelism shared by `run`, `stop` and `remove`
+#[derive(Args)]
+pub struct BulkArgs {
+    /// Select every application carrying this tag (repeatable)
+    #[arg(long)]
+    pub tag: Vec<String>,
+
+    /// Select all applications (run: every manifest app; stop: running apps; remove: installed apps)
+    #[arg(long)]
+    pub all: bool,
+
+    /// Maximum number of applications processed concurrently
+    #[arg(short, long, default_value_t = 4)]
+    pub jobs: usize,
+}
+
 #[derive(Subcommand)]
 pub enum ManifestCommands {
     /// Show manifest information and contents
@@ -112,3 +324,71 @@ pub enum ManifestCommands {
     /// List all accepted manifests
     Accepted,
 }
+
+fn parse_build_arg(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", input)),
+    }
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Delete cached data (everything unless --repos, --manifests or --downloads is given)
+    Clean {
+        /// Only delete git clones used for building
+        #[arg(long)]
+        repos: bool,
+
+        /// Only delete cached manifests (accepted manifests stay trusted)
+        #[arg(long)]
+        manifests: bool,
+
+        /// Only delete checksum-verified Dockerfiles and build contexts
+        #[arg(long)]
+        downloads: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProxyCommands {
+    /// Start Traefik (and the vuln-pkg network) if not already running
+    Start,
+
+    /// Stop and remove Traefik
+    Stop,
+
+    /// Show whether Traefik is running
+    Status,
+
+    /// Recreate Traefik, e.g. to apply a new --domain or --https
+    Restart,
+}
+
+#[derive(Subcommand)]
+pub enum HostsCommands {
+    /// Write every app hostname into the vuln-pkg block of the hosts file
+    Sync {
+        /// Show the resulting block without writing the hosts file
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Hosts file to update
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Remove the vuln-pkg block from the hosts file
+    Clean {
+        /// Show what would be removed without writing the hosts file
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Hosts file to update
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Print the vuln-pkg block without touching the hosts file
+    Print,
+}
@@ -0,0 +1,898 @@
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use hyper::body::Bytes;
+use tar::Builder;
+use tokio::sync::mpsc;
+
+use crate::error::Result;
+use crate::manifest::normalize_relative_path;
+use crate::select::glob_match;
+
+/// Size of the chunks a streamed build context is sent in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// File type bits of a unix mode, and the value marking a symlink
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Exclusion rules from a `.dockerignore` file
+#[derive(Debug, Default)]
+pub struct DockerIgnore {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    /// `!pattern`: re-include what an earlier rule excluded
+    negate: bool,
+    segments: Vec<String>,
+}
+
+impl DockerIgnore {
+    pub fn parse(content: &str) -> Self {
+        let rules = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let (negate, pattern) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest.trim()),
+                    None => (false, line),
+                };
+                let segments: Vec<String> = pattern
+                    .split('/')
+                    .filter(|s| !s.is_empty() && *s != ".")
+                    .map(str::to_string)
+                    .collect();
+                (!segments.is_empty()).then_some(Rule { negate, segments })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether `path` (relative, `/`-separated) is left out of the context.
+    /// As with Docker, the last matching rule wins and a rule matching a
+    /// directory also matches everything below it.
+    pub fn is_excluded(&self, path: &str) -> bool {
+        let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut excluded = false;
+        for rule in &self.rules {
+            let matches = (1..=parts.len()).any(|n| match_segments(&rule.segments, &parts[..n]));
+            if matches {
+                excluded = !rule.negate;
+            }
+        }
+        excluded
+    }
+
+    /// Whether a later `!` rule could re-include something below an
+    /// excluded directory, so the walk has to descend into it anyway
+    fn has_exceptions(&self) -> bool {
+        self.rules.iter().any(|r| r.negate)
+    }
+}
+
+/// Match path segments against pattern segments, where `**` spans any
+/// number of directories and other segments are shell-style globs
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => {
+                glob_match(first, segment) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// A directory sent to Docker as a build context: everything except
+/// `.git` and what `.dockerignore` excludes
+pub struct BuildContext {
+    root: PathBuf,
+    dockerfile: String,
+    ignore: DockerIgnore,
+}
+
+impl BuildContext {
+    pub fn new(root: &Path, dockerfile: &str) -> Result<Self> {
+        let ignore = match fs::read_to_string(root.join(".dockerignore")) {
+            Ok(content) => DockerIgnore::parse(&content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => DockerIgnore::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            dockerfile: dockerfile.trim_start_matches("./").to_string(),
+            ignore,
+        })
+    }
+
+    /// Relative paths of every entry in the context, parents before children
+    pub fn entries(&self) -> Result<Vec<String>> {
+        let mut entries = Vec::new();
+        self.walk(&self.root, "", &mut entries)?;
+        Ok(entries)
+    }
+
+    fn walk(&self, dir: &Path, prefix: &str, entries: &mut Vec<String>) -> Result<()> {
+        let mut children: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+        children.sort_by_key(|entry| entry.file_name());
+
+        for child in children {
+            let name = child.file_name().to_string_lossy().into_owned();
+            if name == ".git" {
+                continue;
+            }
+
+            let path = format!("{}{}", prefix, name);
+            // Docker always needs the Dockerfile and .dockerignore, even if excluded
+            let required = path == self.dockerfile || path == ".dockerignore";
+            let excluded = !required && self.ignore.is_excluded(&path);
+            let is_dir = child.file_type()?.is_dir();
+
+            if !excluded {
+                entries.push(path.clone());
+            }
+            if is_dir && (!excluded || self.ignore.has_exceptions()) {
+                self.walk(&child.path(), &format!("{}/", path), entries)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hash of every entry's path, size and modification time, to notice
+    /// when anything that would be sent to Docker has changed
+    pub fn fingerprint(&self) -> Result<u64> {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for path in self.entries()? {
+            // Files deleted mid-walk just count as a change
+            let metadata = fs::symlink_metadata(self.root.join(&path)).ok();
+            path.hash(&mut hasher);
+            metadata.as_ref().map(|m| m.len()).hash(&mut hasher);
+            metadata.and_then(|m| m.modified().ok()).hash(&mut hasher);
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Write the context as a gzipped tarball
+    pub fn write_tar<W: Write>(&self, writer: W) -> Result<()> {
+        let mut tar = Builder::new(GzEncoder::new(writer, Compression::default()));
+        // Keep symlinks as links instead of copying (or escaping to) their targets
+        tar.follow_symlinks(false);
+
+        for path in self.entries()? {
+            tar.append_path_with_name(self.root.join(&path), &path)?;
+        }
+        tar.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    /// Produce the tarball on a blocking thread, yielding it in chunks as it
+    /// is written. If writing fails the stream ends early and the error is
+    /// returned by the join handle.
+    pub fn stream(self) -> (mpsc::Receiver<Bytes>, tokio::task::JoinHandle<Result<()>>) {
+        let (tx, rx) = mpsc::channel(8);
+        let handle = tokio::task::spawn_blocking(move || {
+            let mut writer = ChannelWriter {
+                tx,
+                buf: Vec::with_capacity(CHUNK_SIZE),
+            };
+            self.write_tar(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        });
+        (rx, handle)
+    }
+}
+
+/// `Write` adapter that forwards fixed-size chunks to an async receiver
+struct ChannelWriter {
+    tx: mpsc::Sender<Bytes>,
+    buf: Vec<u8>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(
+            &mut self.buf,
+            Vec::with_capacity(CHUNK_SIZE),
+        ));
+        self.tx.blocking_send(chunk).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "Docker closed the build upload")
+        })
+    }
+}
+
+/// Archive formats accepted for a downloaded build context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// Detect the format from the first bytes of the archive, ignoring
+    /// whatever extension or content type the server claims
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+            Some(Self::Zip)
+        } else if data.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::TarXz)
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::TarZst)
+        } else if data.get(257..262) == Some(b"ustar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Default for `max_context_size`, in MiB
+const DEFAULT_MAX_CONTEXT_MB: u64 = 1024;
+
+/// Space a tar header takes, counted against the size limit for every
+/// zip entry (tar archives are measured as a whole)
+const TAR_HEADER_SIZE: u64 = 512;
+
+/// Longest symlink target read from a zip archive
+const MAX_LINK_TARGET: u64 = 4096;
+
+/// Largest uncompressed build context `repack_context` accepts, from
+/// `$VULN_PKG_MAX_CONTEXT_MB` (default 1024 MiB)
+pub fn max_context_size() -> std::result::Result<u64, String> {
+    let mb = match std::env::var("VULN_PKG_MAX_CONTEXT_MB") {
+        Ok(value) => value.trim().parse::<u64>().map_err(|_| {
+            format!(
+                "VULN_PKG_MAX_CONTEXT_MB must be a number of MiB, got '{}'",
+                value
+            )
+        })?,
+        Err(_) => DEFAULT_MAX_CONTEXT_MB,
+    };
+    Ok(mb.saturating_mul(1024 * 1024))
+}
+
+fn too_large(max_size: u64) -> String {
+    format!(
+        "archive is larger than {} MiB uncompressed (raise VULN_PKG_MAX_CONTEXT_MB to allow it)",
+        max_size / (1024 * 1024)
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum EntryKind {
+    File,
+    Dir,
+    Symlink(String),
+    Hardlink(String),
+}
+
+/// Convert a downloaded context archive into the gzipped tarball Docker
+/// expects, with `dockerfile` as its Dockerfile. With `strip_root`, the
+/// archive must hold a single top-level directory, which becomes the root.
+///
+/// Entries are copied one at a time, and an archive that unpacks to more
+/// than `max_size` bytes is rejected, so a small download can't exhaust
+/// memory. Entries with absolute paths or `..` components are rejected
+/// rather than skipped, since an archive carrying them is broken or hostile.
+pub fn repack_context(
+    data: &[u8],
+    dockerfile: &str,
+    strip_root: bool,
+    max_size: u64,
+) -> std::result::Result<Vec<u8>, String> {
+    let format = ArchiveFormat::detect(data)
+        .ok_or("unrecognized archive format (expected zip, tar, tar.gz, tar.xz or tar.zst)")?;
+    let mut out = Repacker::new(strip_root);
+    match format {
+        ArchiveFormat::Zip => read_zip(data, max_size, &mut out)?,
+        ArchiveFormat::Tar => read_limited_tar(data, max_size, &mut out)?,
+        ArchiveFormat::TarGz => {
+            read_limited_tar(flate2::read::GzDecoder::new(data), max_size, &mut out)?
+        }
+        ArchiveFormat::TarXz => {
+            // lzma-rs only decompresses xz in one go, into a capped buffer
+            let mut tar = SizeLimit::new(Vec::new(), max_size);
+            if let Err(e) = lzma_rs::xz_decompress(&mut io::BufReader::new(data), &mut tar) {
+                return Err(if tar.exceeded() {
+                    too_large(max_size)
+                } else {
+                    format!("invalid xz data: {}", e)
+                });
+            }
+            read_tar(&tar.inner[..], &mut out)?
+        }
+        ArchiveFormat::TarZst => read_limited_tar(
+            ruzstd::decoding::StreamingDecoder::new(data)
+                .map_err(|e| format!("invalid zstd data: {}", e))?,
+            max_size,
+            &mut out,
+        )?,
+    }
+    out.finish(dockerfile)
+}
+
+/// Writes archive entries into the gzipped tarball for Docker as they are
+/// read, stripping the top-level directory if asked to
+struct Repacker {
+    tar: Builder<GzEncoder<Vec<u8>>>,
+    strip_root: bool,
+    /// Top-level directory being stripped, taken from the first entry
+    root: Option<String>,
+}
+
+impl Repacker {
+    fn new(strip_root: bool) -> Self {
+        Self {
+            tar: Builder::new(GzEncoder::new(Vec::new(), Compression::default())),
+            strip_root,
+            root: None,
+        }
+    }
+
+    /// Append one entry; `data` holds exactly `size` bytes for files
+    fn add<R: Read>(
+        &mut self,
+        path: String,
+        mut kind: EntryKind,
+        mode: u32,
+        size: u64,
+        data: R,
+    ) -> std::result::Result<(), String> {
+        let path = if self.strip_root {
+            match self.strip(&path, &mut kind)? {
+                Some(path) => path,
+                None => return Ok(()),
+            }
+        } else {
+            path
+        };
+        // Replaced by the manifest's Dockerfile in `finish`
+        if path == "Dockerfile" {
+            return Ok(());
+        }
+
+        let io_error = |e: io::Error| e.to_string();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(mode);
+        match &kind {
+            EntryKind::File => {
+                header.set_size(size);
+                self.tar
+                    .append_data(&mut header, &path, ExactReader::new(data, size))
+                    .map_err(io_error)?;
+            }
+            EntryKind::Dir => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                self.tar
+                    .append_data(&mut header, &path, io::empty())
+                    .map_err(io_error)?;
+            }
+            EntryKind::Symlink(target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                self.tar
+                    .append_link(&mut header, &path, target)
+                    .map_err(io_error)?;
+            }
+            EntryKind::Hardlink(target) => {
+                header.set_entry_type(tar::EntryType::Link);
+                header.set_size(0);
+                self.tar
+                    .append_link(&mut header, &path, target)
+                    .map_err(io_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the single top-level directory every entry lives in; None
+    /// for that directory itself
+    fn strip(
+        &mut self,
+        path: &str,
+        kind: &mut EntryKind,
+    ) -> std::result::Result<Option<String>, String> {
+        let top = path.split('/').next().unwrap_or_default();
+        let root = self.root.get_or_insert_with(|| top.to_string());
+        if top != root {
+            return Err(format!(
+                "cannot strip the top-level directory: archive has both '{}' and '{}'",
+                root, top
+            ));
+        }
+
+        let Some((_, rest)) = path.split_once('/') else {
+            // The top-level directory itself (or a file in its place)
+            if *kind == EntryKind::Dir {
+                return Ok(None);
+            }
+            return Err(format!(
+                "cannot strip the top-level directory: '{}' is not a directory",
+                root
+            ));
+        };
+        if let EntryKind::Hardlink(target) = kind {
+            let stripped = target
+                .strip_prefix(&format!("{}/", root))
+                .ok_or_else(|| format!("hard link '{}' points outside '{}'", rest, root))?;
+            *kind = EntryKind::Hardlink(stripped.to_string());
+        }
+        Ok(Some(rest.to_string()))
+    }
+
+    fn finish(mut self, dockerfile: &str) -> std::result::Result<Vec<u8>, String> {
+        let io_error = |e: io::Error| e.to_string();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(dockerfile.len() as u64);
+        header.set_mode(0o644);
+        self.tar
+            .append_data(&mut header, "Dockerfile", dockerfile.as_bytes())
+            .map_err(io_error)?;
+        self.tar
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(io_error)
+    }
+}
+
+/// Reader yielding exactly `remaining` bytes of `inner`, failing if it
+/// ends early, so an entry can't disagree with the size in its header
+struct ExactReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> ExactReader<R> {
+    fn new(inner: R, size: u64) -> Self {
+        Self {
+            inner,
+            remaining: size,
+        }
+    }
+}
+
+impl<R: Read> Read for ExactReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let max = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "entry is shorter than its recorded size",
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Reader or writer that fails once more than `max` bytes pass through it
+struct SizeLimit<T> {
+    inner: T,
+    total: u64,
+    max: u64,
+}
+
+impl<T> SizeLimit<T> {
+    fn new(inner: T, max: u64) -> Self {
+        Self {
+            inner,
+            total: 0,
+            max,
+        }
+    }
+
+    fn exceeded(&self) -> bool {
+        self.total > self.max
+    }
+
+    fn count(&mut self, n: usize) -> io::Result<usize> {
+        self.total += n as u64;
+        if self.exceeded() {
+            return Err(io::Error::other(too_large(self.max)));
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read> Read for SizeLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count(n)
+    }
+}
+
+impl<W: Write> Write for SizeLimit<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(data)?;
+        self.count(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Normalize an archive path; None for the archive root itself
+fn entry_path(raw: &str) -> std::result::Result<Option<String>, String> {
+    match normalize_relative_path(raw) {
+        Some(path) if path.is_empty() => Ok(None),
+        Some(path) => Ok(Some(path)),
+        None => Err(format!(
+            "archive entry '{}' points outside the context",
+            raw
+        )),
+    }
+}
+
+/// Read a tar stream that may unpack to at most `max_size` bytes
+fn read_limited_tar<R: Read>(
+    reader: R,
+    max_size: u64,
+    out: &mut Repacker,
+) -> std::result::Result<(), String> {
+    let mut reader = SizeLimit::new(reader, max_size);
+    read_tar(&mut reader, out).map_err(|e| {
+        if reader.exceeded() {
+            too_large(max_size)
+        } else {
+            e
+        }
+    })
+}
+
+fn read_tar<R: Read>(reader: R, out: &mut Repacker) -> std::result::Result<(), String> {
+    let mut archive = tar::Archive::new(reader);
+    let invalid = |e: io::Error| format!("invalid tar archive: {}", e);
+
+    for entry in archive.entries().map_err(invalid)? {
+        let entry = entry.map_err(invalid)?;
+        let raw = entry
+            .path()
+            .map_err(invalid)?
+            .to_string_lossy()
+            .into_owned();
+        let Some(path) = entry_path(&raw)? else {
+            continue;
+        };
+        let link = || -> std::result::Result<String, String> {
+            let target = entry
+                .link_name()
+                .map_err(invalid)?
+                .ok_or_else(|| format!("link '{}' has no target", raw))?;
+            Ok(target.to_string_lossy().into_owned())
+        };
+
+        let kind = match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+            tar::EntryType::Directory => EntryKind::Dir,
+            tar::EntryType::Symlink => EntryKind::Symlink(link()?),
+            // Hard links name another entry, which has to stay inside too
+            tar::EntryType::Link => match entry_path(&link()?)? {
+                Some(target) => EntryKind::Hardlink(target),
+                None => return Err(format!("hard link '{}' points at the archive root", raw)),
+            },
+            // Devices, fifos and the like have no place in a build context
+            _ => continue,
+        };
+
+        let mode = entry.header().mode().map_err(invalid)? & 0o7777;
+        let size = match kind {
+            EntryKind::File => entry.size(),
+            _ => 0,
+        };
+        out.add(path, kind, mode, size, entry)
+            .map_err(|e| format!("invalid tar archive: {}", e))?;
+    }
+    Ok(())
+}
+
+fn read_zip(data: &[u8], max_size: u64, out: &mut Repacker) -> std::result::Result<(), String> {
+    let invalid = |e: zip::result::ZipError| format!("invalid zip archive: {}", e);
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(invalid)?;
+    let mut total: u64 = 0;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(invalid)?;
+        let Some(path) = entry_path(file.name())? else {
+            continue;
+        };
+        let unix_mode = file.unix_mode();
+        let is_dir = file.is_dir();
+        let size = file.size();
+
+        total = total.saturating_add(size).saturating_add(TAR_HEADER_SIZE);
+        if total > max_size {
+            return Err(too_large(max_size));
+        }
+
+        // Zip archives store a symlink as a file holding its target. Unlike
+        // tar symlinks it is converted by us, so it must stay inside too.
+        if let Some(mode) = unix_mode
+            && mode & S_IFMT == S_IFLNK
+        {
+            let mut target = String::new();
+            (&mut file)
+                .take(MAX_LINK_TARGET)
+                .read_to_string(&mut target)
+                .map_err(|e| format!("invalid zip archive: {}", e))?;
+            if target.starts_with('/') || target.split(['/', '\\']).any(|part| part == "..") {
+                return Err(format!(
+                    "symlink '{}' points outside the context ('{}')",
+                    path, target
+                ));
+            }
+            out.add(path, EntryKind::Symlink(target), 0o777, 0, io::empty())?;
+            continue;
+        }
+
+        if is_dir {
+            let mode = unix_mode.unwrap_or(0o755) & 0o7777;
+            out.add(path, EntryKind::Dir, mode, 0, io::empty())?;
+        } else {
+            let mode = unix_mode.unwrap_or(0o644) & 0o7777;
+            out.add(path, EntryKind::File, mode, size, file)
+                .map_err(|e| format!("invalid zip archive: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dockerignore() {
+        let ignore = DockerIgnore::parse(
+            "# comment\n\
+             node_modules\n\
+             *.log\n\
+             **/*.tmp\n\
+             /docs\n\
+             secrets/*\n\
+             !secrets/README.md\n",
+        );
+
+        assert!(ignore.is_excluded("node_modules"));
+        assert!(ignore.is_excluded("node_modules/lodash/index.js"));
+        assert!(ignore.is_excluded("debug.log"));
+        assert!(!ignore.is_excluded("logs/debug.log"));
+        assert!(ignore.is_excluded("a/b/c.tmp"));
+        assert!(ignore.is_excluded("docs/index.md"));
+        assert!(ignore.is_excluded("secrets/key.pem"));
+        assert!(!ignore.is_excluded("secrets/README.md"));
+        assert!(!ignore.is_excluded("src/main.rs"));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let dir = std::env::temp_dir().join(format!("vuln-pkg-fingerprint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Dockerfile"), "FROM scratch").unwrap();
+        fs::write(dir.join(".dockerignore"), "*.log\n").unwrap();
+        let fingerprint = || {
+            BuildContext::new(&dir, "Dockerfile")
+                .unwrap()
+                .fingerprint()
+                .unwrap()
+        };
+
+        let before = fingerprint();
+        // Ignored files don't trigger a rebuild
+        fs::write(dir.join("debug.log"), "noise").unwrap();
+        let ignored = fingerprint();
+        fs::write(dir.join("app.py"), "print()").unwrap();
+        let added = fingerprint();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(before, ignored);
+        assert_ne!(before, added);
+    }
+
+    #[test]
+    fn test_context_entries() {
+        let dir = std::env::temp_dir().join(format!("vuln-pkg-context-{}", std::process::id()));
+        for path in [".git/HEAD", "src/app.py", "build/out.bin", "vendor/.git"] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x").unwrap();
+        }
+        fs::write(dir.join(".dockerignore"), "build\nDockerfile\n").unwrap();
+        fs::write(dir.join("Dockerfile"), "FROM scratch").unwrap();
+
+        let context = BuildContext::new(&dir, "./Dockerfile").unwrap();
+        let entries = context.entries().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            entries,
+            vec![".dockerignore", "Dockerfile", "src", "src/app.py", "vendor"]
+        );
+    }
+
+    const MAX: u64 = 1024 * 1024;
+
+    fn tar_of(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut tar = Builder::new(Vec::new());
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            if path.ends_with('/') {
+                header.set_entry_type(tar::EntryType::Directory);
+            }
+            // Written raw, so tests can create names Builder would refuse
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            tar.append(&header, content.as_bytes()).unwrap();
+        }
+        tar.into_inner().unwrap()
+    }
+
+    fn repacked_names(tarball: &[u8]) -> Vec<String> {
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball));
+        archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_repack_formats() {
+        let tar = tar_of(&[("app/index.php", "<?php"), ("Dockerfile", "FROM old")]);
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&tar).unwrap();
+        let gz = gz.finish().unwrap();
+
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut &tar[..], &mut xz).unwrap();
+
+        let zst = ruzstd::encoding::compress_to_vec(
+            &tar[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.add_directory("app/", options).unwrap();
+        zip.start_file("app/index.php", options).unwrap();
+        zip.write_all(b"<?php").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        for (archive, format) in [
+            (&tar, ArchiveFormat::Tar),
+            (&gz, ArchiveFormat::TarGz),
+            (&xz, ArchiveFormat::TarXz),
+            (&zst, ArchiveFormat::TarZst),
+            (&zip, ArchiveFormat::Zip),
+        ] {
+            assert_eq!(ArchiveFormat::detect(archive), Some(format));
+            let names =
+                repacked_names(&repack_context(archive, "FROM alpine", false, MAX).unwrap());
+            // The archive's own Dockerfile is replaced
+            assert_eq!(names.iter().filter(|n| *n == "Dockerfile").count(), 1);
+            assert!(names.contains(&"app/index.php".to_string()), "{:?}", format);
+        }
+
+        assert!(repack_context(b"not an archive", "FROM alpine", false, MAX).is_err());
+    }
+
+    #[test]
+    fn test_repack_strip_root() {
+        let tar = tar_of(&[
+            ("./challenge-1.0/", ""),
+            ("challenge-1.0/src/app.py", "print()"),
+        ]);
+        let names = repacked_names(&repack_context(&tar, "FROM alpine", true, MAX).unwrap());
+        assert_eq!(names, vec!["src/app.py", "Dockerfile"]);
+
+        let two_roots = tar_of(&[("a/x", ""), ("b/y", "")]);
+        assert!(repack_context(&two_roots, "FROM alpine", true, MAX).is_err());
+        // Without stripping, several top-level entries are fine
+        assert!(repack_context(&two_roots, "FROM alpine", false, MAX).is_ok());
+    }
+
+    #[test]
+    fn test_repack_rejects_traversal() {
+        for path in ["../etc/cron.d/evil", "/etc/passwd", "app/../../evil"] {
+            let tar = tar_of(&[(path, "x")]);
+            assert!(
+                repack_context(&tar, "FROM alpine", false, MAX).is_err(),
+                "{} was accepted",
+                path
+            );
+        }
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("../evil.sh", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+        assert!(repack_context(&zip, "FROM alpine", false, MAX).is_err());
+    }
+
+    #[test]
+    fn test_repack_size_limit() {
+        let big = "x".repeat(4096);
+        let tar = tar_of(&[("a.bin", big.as_str()), ("b.bin", big.as_str())]);
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&tar).unwrap();
+        let gz = gz.finish().unwrap();
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut &tar[..], &mut xz).unwrap();
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a.bin", "b.bin"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(big.as_bytes()).unwrap();
+        }
+        let zip = zip.finish().unwrap().into_inner();
+
+        for archive in [&tar, &gz, &xz, &zip] {
+            let error = repack_context(archive, "FROM alpine", false, 6000).unwrap_err();
+            assert!(error.contains("larger than"), "{}", error);
+            assert!(repack_context(archive, "FROM alpine", false, MAX).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_repack_rejects_escaping_zip_symlinks() {
+        let zip_with_link = |target: &str| {
+            let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            zip.add_symlink("app/link", target, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.finish().unwrap().into_inner()
+        };
+
+        for target in ["/etc/passwd", "../../etc/passwd", "a/../../b"] {
+            assert!(
+                repack_context(&zip_with_link(target), "FROM alpine", false, MAX).is_err(),
+                "{} was accepted",
+                target
+            );
+        }
+        let names = repacked_names(
+            &repack_context(&zip_with_link("index.php"), "FROM alpine", false, MAX).unwrap(),
+        );
+        assert!(names.contains(&"app/link".to_string()));
+    }
+}
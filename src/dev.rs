@@ -0,0 +1,210 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::{Result, VulnPkgError};
+use crate::manifest::{App, PackageType, PortConfig, PortEntry, Protocol};
+
+/// How often `vuln-pkg dev` checks the working directory for changes
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Image version (tag) of apps built by `vuln-pkg dev`
+const DEV_VERSION: &str = "dev";
+
+/// Settings for `vuln-pkg dev`, taken from the command line
+pub struct DevOptions {
+    pub name: Option<String>,
+    pub ports: Vec<PortConfig>,
+    pub dockerfile: String,
+    pub build_args: Vec<(String, String)>,
+}
+
+/// A `type: local` app for the working directory `dir`. Ports default to
+/// the Dockerfile's `EXPOSE` instructions, and to HTTP on 80 without any.
+pub fn dev_app(dir: &Path, options: DevOptions) -> Result<App> {
+    let dir = dir.canonicalize()?;
+    if !dir.is_dir() {
+        return Err(VulnPkgError::ManifestValidation(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+
+    let mut app = App {
+        name: options.name.unwrap_or_else(|| app_name_for(&dir)),
+        version: DEV_VERSION.to_string(),
+        package_type: PackageType::Local,
+        path: Some(".".to_string()),
+        base_dir: Some(dir.clone()),
+        dockerfile_path: Some(options.dockerfile),
+        build_args: options.build_args.into_iter().collect(),
+        ..Default::default()
+    };
+    app.validate()?;
+
+    let dockerfile = app.context_dockerfile()?;
+    let content = std::fs::read_to_string(dir.join(&dockerfile)).map_err(|_| {
+        VulnPkgError::DockerfileNotFound {
+            repo: dir.display().to_string(),
+            path: dockerfile,
+        }
+    })?;
+
+    let ports = match options.ports {
+        ports if !ports.is_empty() => ports,
+        _ => match exposed_ports(&content) {
+            ports if !ports.is_empty() => ports,
+            _ => vec![PortConfig::http(80)],
+        },
+    };
+    app.ports = ports.into_iter().map(PortEntry::Config).collect();
+    Ok(app)
+}
+
+/// Hostname-safe app name derived from a directory name
+fn app_name_for(dir: &Path) -> String {
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = name.trim_matches('-');
+    if name.is_empty() {
+        "dev".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Ports from `EXPOSE` instructions. Docker's default protocol is TCP, but
+/// most challenges are web apps, so plain ports are routed as HTTP.
+fn exposed_ports(dockerfile: &str) -> Vec<PortConfig> {
+    let mut ports: Vec<PortConfig> = Vec::new();
+    for line in dockerfile.lines() {
+        let mut words = line.split_whitespace();
+        if !words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case("EXPOSE"))
+        {
+            continue;
+        }
+        // Variables (`EXPOSE $PORT`) can't be resolved here and are skipped
+        for port in words.filter_map(|w| parse_port(w).ok()) {
+            if !ports.iter().any(|p| p.port == port.port) {
+                ports.push(port);
+            }
+        }
+    }
+    ports
+}
+
+/// Parse a `--port` value: `8080` (HTTP), `8080/http`, `3306/tcp` or `53/udp`
+pub fn parse_port(input: &str) -> std::result::Result<PortConfig, String> {
+    let (port, protocol) = match input.split_once('/') {
+        Some((port, protocol)) => (port, protocol.to_lowercase()),
+        None => (input, "http".to_string()),
+    };
+    let port: u16 = port
+        .parse()
+        .ok()
+        .filter(|p| *p > 0)
+        .ok_or_else(|| format!("invalid port '{}'", port))?;
+
+    let protocol = match protocol.as_str() {
+        "http" => Protocol::Http,
+        "tcp" => Protocol::Tcp,
+        "udp" => Protocol::Udp,
+        _ => {
+            return Err(format!(
+                "invalid protocol '{}' (expected http, tcp or udp)",
+                protocol
+            ));
+        }
+    };
+    Ok(PortConfig {
+        port,
+        protocol,
+        label: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_port() {
+        assert_eq!(parse_port("8080").unwrap(), PortConfig::http(8080));
+        assert_eq!(parse_port("3306/TCP").unwrap().protocol, Protocol::Tcp);
+        assert_eq!(parse_port("53/udp").unwrap().protocol, Protocol::Udp);
+        assert!(parse_port("0").is_err());
+        assert!(parse_port("http").is_err());
+        assert!(parse_port("80/sctp").is_err());
+    }
+
+    #[test]
+    fn test_exposed_ports() {
+        let ports = exposed_ports(
+            "FROM php:7-apache\n\
+             EXPOSE 80\n\
+             expose 3306/tcp 53/udp $EXTRA\n\
+             EXPOSE 80/tcp\n",
+        );
+        assert_eq!(
+            ports,
+            vec![
+                PortConfig::http(80),
+                PortConfig::tcp(3306),
+                PortConfig::udp(53)
+            ]
+        );
+        assert!(exposed_ports("FROM scratch\n").is_empty());
+    }
+
+    #[test]
+    fn test_app_name_for() {
+        assert_eq!(
+            app_name_for(Path::new("/src/My_Challenge 2")),
+            "my-challenge-2"
+        );
+        assert_eq!(app_name_for(Path::new("/")), "dev");
+    }
+
+    #[test]
+    fn test_dev_app() {
+        let dir = std::env::temp_dir().join(format!("vuln-pkg-dev-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Dockerfile"), "FROM nginx\nEXPOSE 8080\n").unwrap();
+
+        let options = |ports: Vec<PortConfig>| DevOptions {
+            name: Some("sqli-lab".to_string()),
+            ports,
+            dockerfile: "Dockerfile".to_string(),
+            build_args: vec![("FLAG".to_string(), "ctf{x}".to_string())],
+        };
+        let app = dev_app(&dir, options(Vec::new())).unwrap();
+        let overridden = dev_app(&dir, options(vec![PortConfig::tcp(9000)])).unwrap();
+        let missing = dev_app(
+            &dir,
+            DevOptions {
+                dockerfile: "docker/Dockerfile".to_string(),
+                ..options(Vec::new())
+            },
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(app.effective_image(), "vuln-pkg/sqli-lab:dev");
+        assert_eq!(app.port_configs(), vec![PortConfig::http(8080)]);
+        assert_eq!(
+            app.build_args.get("FLAG").map(String::as_str),
+            Some("ctf{x}")
+        );
+        assert_eq!(overridden.port_configs(), vec![PortConfig::tcp(9000)]);
+        assert!(matches!(
+            missing,
+            Err(VulnPkgError::DockerfileNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,342 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::error::{Result, VulnPkgError};
+use crate::output::Output;
+
+/// TTL for answers we generate ourselves
+const ANSWER_TTL: u32 = 60;
+/// How long to wait for the upstream resolver before dropping a query
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest UDP DNS message we accept (EDNS allows more than the classic 512)
+const MAX_PACKET: usize = 4096;
+
+const HEADER_LEN: usize = 12;
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const RCODE_REFUSED: u16 = 5;
+
+/// A minimal DNS responder: names under `domain` resolve to `address`,
+/// everything else is forwarded upstream (or refused when offline)
+pub struct DnsResponder {
+    domain: String,
+    address: Ipv4Addr,
+    upstream: Option<SocketAddr>,
+}
+
+/// The parts of a query we need to answer it
+struct Question {
+    id: u16,
+    flags: u16,
+    name: String,
+    qtype: u16,
+    qclass: u16,
+    /// Offset of the first byte after the question section
+    end: usize,
+}
+
+impl DnsResponder {
+    pub fn new(domain: &str, address: Ipv4Addr, upstream: Option<SocketAddr>) -> Self {
+        Self {
+            domain: domain.trim_end_matches('.').to_lowercase(),
+            address,
+            upstream,
+        }
+    }
+
+    /// Serve queries on `listen` until the process is interrupted
+    pub async fn serve(self, listen: SocketAddr, output: &Output) -> Result<()> {
+        let socket = UdpSocket::bind(listen).await.map_err(|e| {
+            VulnPkgError::DnsServer(format!(
+                "Failed to bind {}: {} (port 53 usually needs root; try --listen 127.0.0.1:5353)",
+                listen, e
+            ))
+        })?;
+        let socket = Arc::new(socket);
+        let responder = Arc::new(self);
+
+        output.dns_listening(
+            listen,
+            &responder.domain,
+            responder.address,
+            responder.upstream,
+        );
+
+        let mut buf = vec![0u8; MAX_PACKET];
+        loop {
+            // A failed receive only affects one packet; on Windows an ICMP
+            // port-unreachable from an earlier reply shows up as ConnectionReset
+            let (len, client) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    output.debug(&format!("receive failed: {}", e));
+                    continue;
+                }
+            };
+            let query = buf[..len].to_vec();
+
+            if let Some(response) = responder.answer_local(&query) {
+                output.debug(&format!("{}: answered locally", client));
+                let _ = socket.send_to(&response, client).await;
+                continue;
+            }
+
+            let Some(upstream) = responder.upstream else {
+                if let Some(response) = refuse(&query) {
+                    let _ = socket.send_to(&response, client).await;
+                }
+                continue;
+            };
+
+            let socket = Arc::clone(&socket);
+            tokio::spawn(async move {
+                match forward(&query, upstream).await {
+                    Ok(response) => {
+                        let _ = socket.send_to(&response, client).await;
+                    }
+                    Err(e) => tracing::debug!("forwarding query from {} failed: {}", client, e),
+                }
+            });
+        }
+    }
+
+    /// Build a response for queries under the lab domain.
+    /// Returns None for anything that should be forwarded.
+    fn answer_local(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let question = parse_question(query)?;
+        if !self.is_local(&question.name) {
+            return None;
+        }
+
+        let answers =
+            question.qclass == CLASS_IN && (question.qtype == TYPE_A || question.qtype == TYPE_ANY);
+
+        // QR, AA and RA set; keep the client's RD bit. NOERROR with no
+        // answers (NODATA) for other record types such as AAAA.
+        let flags = 0x8000 | 0x0400 | (question.flags & 0x0100) | 0x0080;
+
+        let mut response = Vec::with_capacity(question.end + 16);
+        response.extend_from_slice(&question.id.to_be_bytes());
+        response.extend_from_slice(&flags.to_be_bytes());
+        response.extend_from_slice(&1u16.to_be_bytes());
+        response.extend_from_slice(&u16::from(answers).to_be_bytes());
+        response.extend_from_slice(&0u16.to_be_bytes());
+        response.extend_from_slice(&0u16.to_be_bytes());
+        response.extend_from_slice(&query[HEADER_LEN..question.end]);
+
+        if answers {
+            // Name is a pointer back to the question
+            response.extend_from_slice(&0xC00Cu16.to_be_bytes());
+            response.extend_from_slice(&TYPE_A.to_be_bytes());
+            response.extend_from_slice(&CLASS_IN.to_be_bytes());
+            response.extend_from_slice(&ANSWER_TTL.to_be_bytes());
+            response.extend_from_slice(&4u16.to_be_bytes());
+            response.extend_from_slice(&self.address.octets());
+        }
+
+        Some(response)
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_lowercase();
+        name == self.domain || name.ends_with(&format!(".{}", self.domain))
+    }
+}
+
+/// Parse the header and single question of a standard query
+fn parse_question(packet: &[u8]) -> Option<Question> {
+    if packet.len() < HEADER_LEN {
+        return None;
+    }
+
+    let id = u16::from_be_bytes([packet[0], packet[1]]);
+    let flags = u16::from_be_bytes([packet[2], packet[3]]);
+    let qdcount = u16::from_be_bytes([packet[4], packet[5]]);
+
+    // Only plain queries (QR = 0, OPCODE = 0) with exactly one question
+    if flags & 0x8000 != 0 || (flags >> 11) & 0xF != 0 || qdcount != 1 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut pos = HEADER_LEN;
+    loop {
+        let len = *packet.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers never appear in a well-formed question
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        let label = packet.get(pos..pos + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += len;
+    }
+
+    let qtype = u16::from_be_bytes([*packet.get(pos)?, *packet.get(pos + 1)?]);
+    let qclass = u16::from_be_bytes([*packet.get(pos + 2)?, *packet.get(pos + 3)?]);
+
+    Some(Question {
+        id,
+        flags,
+        name: labels.join("."),
+        qtype,
+        qclass,
+        end: pos + 4,
+    })
+}
+
+/// Build a REFUSED response (used when forwarding is disabled)
+fn refuse(query: &[u8]) -> Option<Vec<u8>> {
+    let question = parse_question(query)?;
+    let flags = 0x8000 | (question.flags & 0x0100) | 0x0080 | RCODE_REFUSED;
+
+    let mut response = Vec::with_capacity(question.end);
+    response.extend_from_slice(&question.id.to_be_bytes());
+    response.extend_from_slice(&flags.to_be_bytes());
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&[0; 6]);
+    response.extend_from_slice(&query[HEADER_LEN..question.end]);
+    Some(response)
+}
+
+/// Relay a query to the upstream resolver and return its raw response.
+/// Datagrams from other senders or with another transaction ID are dropped,
+/// so a spoofed or stale packet can't be passed back to the client.
+async fn forward(query: &[u8], upstream: SocketAddr) -> std::io::Result<Vec<u8>> {
+    let bind: SocketAddr = if upstream.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.send_to(query, upstream).await?;
+
+    let mut buf = vec![0u8; MAX_PACKET];
+    let receive = async {
+        loop {
+            let (len, from) = socket.recv_from(&mut buf).await?;
+            if from == upstream && len >= 2 && query.get(..2) == Some(&buf[..2]) {
+                return Ok::<_, std::io::Error>(len);
+            }
+        }
+    };
+    let len = tokio::time::timeout(UPSTREAM_TIMEOUT, receive)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "upstream timed out"))??;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut packet = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet
+    }
+
+    fn responder() -> DnsResponder {
+        DnsResponder::new("lab.local", Ipv4Addr::new(10, 0, 0, 5), None)
+    }
+
+    #[test]
+    fn test_answers_wildcard_a_record() {
+        let q = query("DVWA.lab.local", TYPE_A);
+        let response = responder().answer_local(&q).unwrap();
+
+        assert_eq!(&response[0..2], &[0x12, 0x34]);
+        // QR + AA + RD + RA, NOERROR
+        assert_eq!(&response[2..4], &[0x85, 0x80]);
+        // One answer
+        assert_eq!(&response[6..8], &[0, 1]);
+        assert_eq!(&response[response.len() - 4..], &[10, 0, 0, 5]);
+    }
+
+    #[test]
+    fn test_answers_domain_apex() {
+        assert!(
+            responder()
+                .answer_local(&query("lab.local", TYPE_A))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_aaaa_gets_empty_answer() {
+        let response = responder()
+            .answer_local(&query("dvwa.lab.local", 28))
+            .unwrap();
+        assert_eq!(&response[6..8], &[0, 0]);
+        assert_eq!(response.len(), query("dvwa.lab.local", 28).len());
+    }
+
+    #[test]
+    fn test_foreign_names_are_forwarded() {
+        assert!(
+            responder()
+                .answer_local(&query("example.com", TYPE_A))
+                .is_none()
+        );
+        assert!(
+            responder()
+                .answer_local(&query("notlab.local", TYPE_A))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_malformed_packets_are_ignored() {
+        assert!(responder().answer_local(&[0x12, 0x34]).is_none());
+        let mut truncated = query("dvwa.lab.local", TYPE_A);
+        truncated.truncate(truncated.len() - 3);
+        assert!(responder().answer_local(&truncated).is_none());
+    }
+
+    #[test]
+    fn test_refuse_sets_rcode() {
+        let response = refuse(&query("example.com", TYPE_A)).unwrap();
+        assert_eq!(response[3] & 0x0F, RCODE_REFUSED as u8);
+    }
+
+    #[tokio::test]
+    async fn test_forward_ignores_mismatched_replies() {
+        let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut buf = vec![0u8; MAX_PACKET];
+            let (len, client) = upstream.recv_from(&mut buf).await.unwrap();
+            let mut reply = buf[..len].to_vec();
+            reply[2] |= 0x80;
+
+            // A different sender and a different ID are both dropped
+            stranger.send_to(&reply, client).await.unwrap();
+            let mut wrong_id = reply.clone();
+            wrong_id[0] ^= 0xFF;
+            upstream.send_to(&wrong_id, client).await.unwrap();
+            upstream.send_to(&reply, client).await.unwrap();
+            reply
+        });
+
+        let response = forward(&query("example.com", TYPE_A), upstream_addr)
+            .await
+            .unwrap();
+        assert_eq!(response, server.await.unwrap());
+    }
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use bollard::Docker;
 use bollard::container::{
@@ -7,20 +7,27 @@ use bollard::container::{
     StartContainerOptions, StopContainerOptions,
 };
 use bollard::image::{BuildImageOptions, CreateImageOptions};
-use bollard::models::{EndpointSettings, HostConfig, Mount, MountTypeEnum, PortBinding};
+use bollard::models::{
+    BuildInfo, EndpointSettings, HostConfig, Mount, MountTypeEnum, PortBinding, PortTypeEnum,
+};
 use bollard::network::{CreateNetworkOptions, ListNetworksOptions};
 use flate2::Compression;
 use flate2::write::GzEncoder;
 use futures::StreamExt;
-use git2::Repository;
+use git2::{AutotagOption, FetchOptions, Repository, SubmoduleUpdateOptions};
+use sha2::{Digest, Sha256};
 use tar::Builder;
 
+use crate::context::{BuildContext, max_context_size, repack_context};
+use crate::credentials::{GitCredentials, url_host};
 use crate::error::{Result, VulnPkgError};
-use crate::manifest::{App, Protocol};
-use crate::output::Output;
+use crate::manifest::{App, Protocol, is_commit_sha};
+use crate::output::{Output, Progress};
 use crate::state::{AllocatedPort, StateManager};
 
 const CONTAINER_LABEL: &str = "vuln-pkg";
+/// Hash of the settings a container was created with (see `container_config_hash`)
+const CONFIG_LABEL: &str = "vuln-pkg.config";
 const NETWORK_NAME: &str = "vuln-pkg";
 const TRAEFIK_IMAGE: &str = "traefik:v3.0";
 const TRAEFIK_CONTAINER: &str = "vuln-pkg-traefik";
@@ -29,12 +36,58 @@ pub struct DockerManager {
     docker: Docker,
 }
 
+/// Options for building a dockerfile/git package, from its manifest entry
+/// plus any `rebuild` overrides
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    pub build_args: HashMap<String, String>,
+    pub target: Option<String>,
+    pub platform: Option<String>,
+    pub no_cache: bool,
+    pub labels: HashMap<String, String>,
+}
+
+impl BuildOptions {
+    pub fn for_app(app: &App) -> Self {
+        Self {
+            build_args: app.build_args.clone().into_iter().collect(),
+            target: app.target.clone(),
+            platform: app.platform.clone(),
+            no_cache: app.no_cache,
+            labels: app.labels.clone().into_iter().collect(),
+        }
+    }
+}
+
+/// An app container recovered from Docker, independent of `state.json`
+#[derive(Debug, Clone)]
+pub struct ManagedContainer {
+    /// Container ID
+    pub id: String,
+    /// App name from the `vuln-pkg` label
+    pub app: String,
+    /// Whether the container is currently running
+    pub running: bool,
+    /// Image the container was created from
+    pub image: Option<String>,
+    /// HTTP hostnames recovered from the Traefik router labels
+    pub hostnames: Vec<String>,
+    /// TCP/UDP port bindings published on the host
+    pub allocated_ports: Vec<AllocatedPort>,
+}
+
 impl DockerManager {
     pub fn new() -> Result<Self> {
         let docker = Docker::connect_with_local_defaults()?;
         Ok(Self { docker })
     }
 
+    /// Query the Docker daemon version (also verifies connectivity)
+    pub async fn server_version(&self) -> Result<String> {
+        let version = self.docker.version().await?;
+        Ok(version.version.unwrap_or_else(|| "unknown".to_string()))
+    }
+
     // ==================== Network Management ====================
 
     pub async fn ensure_network(&self) -> Result<String> {
@@ -68,7 +121,25 @@ impl DockerManager {
         Ok(response.id)
     }
 
-    #[allow(dead_code)]
+    /// Check whether the vuln-pkg network exists without creating it
+    pub async fn network_exists(&self) -> Result<bool> {
+        Ok(self.find_network().await?.is_some())
+    }
+
+    /// Look up the vuln-pkg network ID without creating it
+    pub async fn find_network(&self) -> Result<Option<String>> {
+        let mut filters = HashMap::new();
+        filters.insert("name", vec![NETWORK_NAME]);
+
+        let options = ListNetworksOptions { filters };
+        let networks = self.docker.list_networks(Some(options)).await?;
+
+        Ok(networks
+            .into_iter()
+            .find(|n| n.name.as_deref() == Some(NETWORK_NAME))
+            .and_then(|n| n.id))
+    }
+
     pub async fn remove_network(&self, network_id: &str) -> Result<()> {
         self.docker.remove_network(network_id).await?;
         Ok(())
@@ -77,6 +148,20 @@ impl DockerManager {
     // ==================== Traefik Management ====================
 
     pub async fn is_traefik_running(&self) -> Result<Option<String>> {
+        match self.find_traefik_container().await? {
+            Some((id, true)) => Ok(Some(id)),
+            Some((id, false)) => {
+                // Container exists but not running - remove and recreate
+                self.remove_container(&id).await?;
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Look up the Traefik container without touching it
+    /// Returns (container_id, is_running) if found, None if not found
+    pub async fn find_traefik_container(&self) -> Result<Option<(String, bool)>> {
         let mut filters = HashMap::new();
         filters.insert("name", vec![TRAEFIK_CONTAINER]);
 
@@ -93,17 +178,10 @@ impl DockerManager {
                 && names
                     .iter()
                     .any(|n| n == &format!("/{}", TRAEFIK_CONTAINER))
+                && let Some(id) = container.id
             {
                 let running = container.state.as_deref() == Some("running");
-                if running {
-                    return Ok(container.id);
-                } else {
-                    // Container exists but not running - remove and recreate
-                    if let Some(id) = &container.id {
-                        self.remove_container(id).await?;
-                    }
-                    return Ok(None);
-                }
+                return Ok(Some((id, running)));
             }
         }
 
@@ -244,6 +322,15 @@ impl DockerManager {
                 Ok(info) => {
                     if let Some(status) = info.status {
                         output.debug(&status);
+                        let detail = info.progress_detail.unwrap_or_default();
+                        output.progress(Progress {
+                            action: "pull",
+                            image: image.to_string(),
+                            layer: info.id,
+                            status,
+                            current: detail.current,
+                            total: detail.total,
+                        });
                     }
                 }
                 Err(e) => return Err(VulnPkgError::Docker(e)),
@@ -264,6 +351,35 @@ impl DockerManager {
         }
     }
 
+    /// Registry digest the local image was pulled with, or None if it was
+    /// built locally or is missing
+    pub async fn image_digest(&self, image: &str) -> Result<Option<String>> {
+        match self.docker.inspect_image(image).await {
+            Ok(info) => Ok(repo_digest(image, &info.repo_digests.unwrap_or_default())),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(VulnPkgError::Docker(e)),
+        }
+    }
+
+    /// Digest the registry currently serves for `image`
+    pub async fn registry_digest(&self, image: &str) -> Result<Option<String>> {
+        let info = self.docker.inspect_registry_image(image, None).await?;
+        Ok(info.descriptor.digest)
+    }
+
+    /// Size of a local image in bytes, or None if it has not been pulled/built
+    pub async fn image_size(&self, image: &str) -> Result<Option<i64>> {
+        match self.docker.inspect_image(image).await {
+            Ok(info) => Ok(info.size),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(VulnPkgError::Docker(e)),
+        }
+    }
+
     #[allow(dead_code)]
     pub async fn remove_image(&self, image: &str) -> Result<()> {
         self.docker.remove_image(image, None, None).await?;
@@ -277,74 +393,149 @@ impl DockerManager {
         &self,
         dockerfile_content: &str,
         image_tag: &str,
+        build: &BuildOptions,
         output: &Output,
     ) -> Result<()> {
         output.info(&format!("Building image: {}", image_tag));
 
         let tar_bytes = Self::create_dockerfile_tarball(dockerfile_content)?;
-        self.build_image_from_tarball(&tar_bytes, image_tag, "Dockerfile", output)
+        let options = Self::build_options(image_tag, "Dockerfile", build);
+        self.build_image_from_tarball(tar_bytes, options, output)
             .await
     }
 
     /// Build an image from a remote Dockerfile URL with optional context
     pub async fn build_from_dockerfile_url(
         &self,
-        dockerfile_url: &str,
-        context_url: Option<&str>,
-        image_tag: &str,
+        app: &App,
+        build: &BuildOptions,
+        state_mgr: &StateManager,
         output: &Output,
     ) -> Result<()> {
-        output.info(&format!("Fetching Dockerfile from: {}", dockerfile_url));
+        let dockerfile_url = app.dockerfile_url.as_deref().ok_or_else(|| {
+            VulnPkgError::ManifestValidation(format!(
+                "Dockerfile app '{}' missing dockerfile_url field",
+                app.name
+            ))
+        })?;
+        let image_tag = app.effective_image();
+        let downloads = state_mgr.downloads_dir();
 
-        // Fetch the Dockerfile
-        let dockerfile_content = reqwest::get(dockerfile_url)
-            .await
-            .map_err(|e| VulnPkgError::DockerfileFetch {
-                url: dockerfile_url.to_string(),
-                source: e,
-            })?
-            .text()
-            .await
-            .map_err(|e| VulnPkgError::DockerfileFetch {
-                url: dockerfile_url.to_string(),
-                source: e,
-            })?;
-
-        let tar_bytes = if let Some(ctx_url) = context_url {
+        output.info(&format!("Fetching Dockerfile from: {}", dockerfile_url));
+        let dockerfile = fetch_artifact(
+            dockerfile_url,
+            app.dockerfile_sha256.as_deref(),
+            &downloads,
+            |url, source| VulnPkgError::DockerfileFetch { url, source },
+            output,
+        )
+        .await?;
+        let dockerfile_content = String::from_utf8_lossy(&dockerfile);
+
+        let tar_bytes = if let Some(ctx_url) = app.context_url.as_deref() {
             output.info(&format!("Fetching build context from: {}", ctx_url));
-            Self::fetch_and_merge_context(&dockerfile_content, ctx_url).await?
+            let context = fetch_artifact(
+                ctx_url,
+                app.context_sha256.as_deref(),
+                &downloads,
+                |url, source| VulnPkgError::ContextFetch { url, source },
+                output,
+            )
+            .await?;
+            // Whatever the archive format, Docker gets a gzipped tarball
+            max_context_size()
+                .and_then(|max_size| {
+                    repack_context(
+                        &context,
+                        &dockerfile_content,
+                        app.context_strip_root,
+                        max_size,
+                    )
+                })
+                .map_err(|message| VulnPkgError::ContextArchive {
+                    url: ctx_url.to_string(),
+                    message,
+                })?
         } else {
             Self::create_dockerfile_tarball(&dockerfile_content)?
         };
 
         output.info(&format!("Building image: {}", image_tag));
-        self.build_image_from_tarball(&tar_bytes, image_tag, "Dockerfile", output)
+        let options = Self::build_options(&image_tag, "Dockerfile", build);
+        self.build_image_from_tarball(tar_bytes, options, output)
+            .await
+    }
+
+    /// Build an image from a directory on disk (`type: local` packages)
+    pub async fn build_from_local(
+        &self,
+        app: &App,
+        build: &BuildOptions,
+        output: &Output,
+    ) -> Result<()> {
+        let root = app.local_dir()?;
+        let dockerfile_path = app.context_dockerfile()?;
+        if !root.join(&dockerfile_path).is_file() {
+            return Err(VulnPkgError::DockerfileNotFound {
+                repo: root.display().to_string(),
+                path: dockerfile_path,
+            });
+        }
+
+        let image_tag = app.effective_image();
+        output.info(&format!(
+            "Building image: {} (from {})",
+            image_tag,
+            root.display()
+        ));
+        let context = BuildContext::new(&root, &dockerfile_path)?;
+        let options = Self::build_options(&image_tag, &dockerfile_path, build);
+        self.build_image_from_context(context, options, output)
             .await
     }
 
     /// Build an image from a cloned Git repository
     pub async fn build_from_git(
         &self,
-        repo_url: &str,
-        git_ref: Option<&str>,
-        dockerfile_path: Option<&str>,
-        image_tag: &str,
+        app: &App,
+        build: &BuildOptions,
         state_mgr: &StateManager,
         output: &Output,
     ) -> Result<Option<String>> {
+        let repo_url = app.repo.as_deref().ok_or_else(|| {
+            VulnPkgError::ManifestValidation(format!("Git app '{}' missing repo field", app.name))
+        })?;
+        let image_tag = app.effective_image();
         output.info(&format!("Cloning repository: {}", repo_url));
 
-        // Clone to a directory under ~/.vuln-pkg/repos/
-        let repo_name = Self::sanitize_repo_name(repo_url);
-        let clone_dir = state_mgr.repos_dir().join(&repo_name);
+        // Clone to ~/.vuln-pkg/repos/<app>@<ref>/, so apps building other
+        // refs of the same repository never share a working tree
+        let clone_dir = state_mgr
+            .repos_dir()
+            .join(clone_dir_name(&app.name, app.git_ref.as_deref()));
 
         // Clone or open existing repository
-        let repo = Self::clone_or_open_repo(repo_url, &clone_dir)?;
+        let credentials = GitCredentials::load(&state_mgr.credentials_file())?;
+        let repo = Self::clone_or_open_repo(
+            repo_url,
+            &clone_dir,
+            app.git_ref.as_deref(),
+            app.depth,
+            &credentials,
+        )?;
+
+        // Checkout the specified ref, or the remote's default branch
+        match app.git_ref.as_deref() {
+            Some(ref_name) => {
+                output.info(&format!("Checking out: {}", ref_name));
+                Self::checkout_ref(&repo, ref_name)?;
+            }
+            None => Self::checkout_ref(&repo, "HEAD")?,
+        }
 
-        // Checkout the specified ref if provided
-        if let Some(ref_name) = git_ref {
-            output.info(&format!("Checking out: {}", ref_name));
-            Self::checkout_ref(&repo, ref_name)?;
+        if app.submodules {
+            output.info("Updating submodules");
+            Self::update_submodules(&repo, &credentials)?;
         }
 
         // Get current commit SHA
@@ -354,57 +545,213 @@ impl DockerManager {
             .and_then(|h| h.target())
             .map(|oid| oid.to_string());
 
-        let dockerfile_path = dockerfile_path.unwrap_or("Dockerfile");
-        output.info(&format!("Building from {}", dockerfile_path));
+        // A pinned commit guards against a moved branch or re-pointed tag
+        if let Some(expected) = &app.commit {
+            let found = commit_sha.clone().unwrap_or_default();
+            if !commit_matches(&found, expected) {
+                return Err(VulnPkgError::CommitMismatch {
+                    repo: repo_url.to_string(),
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+            output.info(&format!("Verified pinned commit {}", expected));
+        }
 
-        // Create tarball from the repo directory
-        let tar_bytes = Self::create_context_tarball(&clone_dir, dockerfile_path)?;
+        let context_dir = app.context_dir()?;
+        let dockerfile_path = app.context_dockerfile()?;
+        let context_root = Self::checkout_path(&clone_dir, &context_dir, repo_url)?;
+        let dockerfile = Self::checkout_path(&context_root, &dockerfile_path, repo_url)?;
+        if !dockerfile.is_file() {
+            return Err(VulnPkgError::DockerfileNotFound {
+                repo: repo_url.to_string(),
+                path: Path::new(&context_dir)
+                    .join(&dockerfile_path)
+                    .to_string_lossy()
+                    .into_owned(),
+            });
+        }
+
+        if context_dir.is_empty() {
+            output.info(&format!("Building from {}", dockerfile_path));
+        } else {
+            output.info(&format!(
+                "Building from {} (context: {})",
+                dockerfile_path, context_dir
+            ));
+        }
+
+        // .git and anything in .dockerignore stay out of the context
+        let context = BuildContext::new(&context_root, &dockerfile_path)?;
 
         output.info(&format!("Building image: {}", image_tag));
-        self.build_image_from_tarball(&tar_bytes, image_tag, dockerfile_path, output)
+        let options = Self::build_options(&image_tag, &dockerfile_path, build);
+        self.build_image_from_context(context, options, output)
             .await?;
 
         Ok(commit_sha)
     }
 
-    /// Core build method using bollard
-    async fn build_image_from_tarball(
+    /// Build from a directory context, streaming the tarball to Docker while
+    /// it is being written so large contexts are never held in memory
+    async fn build_image_from_context(
         &self,
-        tar_bytes: &[u8],
-        image_tag: &str,
-        dockerfile_path: &str,
+        context: BuildContext,
+        options: BuildImageOptions<String>,
         output: &Output,
     ) -> Result<()> {
-        let options = BuildImageOptions {
-            dockerfile: dockerfile_path,
-            t: image_tag,
+        #[cfg(unix)]
+        if let Some(socket) = docker_socket() {
+            return Self::build_image_streaming(&socket, &options, context, output).await;
+        }
+
+        // bollard can only upload a complete body, so buffer the context
+        // when Docker is not reachable over a local socket
+        let mut tar_bytes = Vec::new();
+        context.write_tar(&mut tar_bytes)?;
+        self.build_image_from_tarball(tar_bytes, options, output)
+            .await
+    }
+
+    /// POST /build over the Docker socket with a streamed request body
+    #[cfg(unix)]
+    async fn build_image_streaming(
+        socket: &Path,
+        options: &BuildImageOptions<String>,
+        context: BuildContext,
+        output: &Output,
+    ) -> Result<()> {
+        use http_body_util::{BodyExt, StreamBody};
+        use hyper::Request;
+        use hyper::body::Frame;
+        use hyper::header::{CONTENT_TYPE, HOST};
+        use hyper_util::rt::TokioIo;
+
+        let image_tag = options.t.as_str();
+        let build_error = |message: String| VulnPkgError::ImageBuild {
+            image: image_tag.to_string(),
+            message,
+        };
+
+        let query = serde_urlencoded::to_string(options).map_err(|e| build_error(e.to_string()))?;
+        // Reported like bollard's own connection failures (docker_unavailable)
+        let stream = tokio::net::UnixStream::connect(socket)
+            .await
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => {
+                    bollard::errors::Error::SocketNotFoundError(socket.display().to_string())
+                }
+                _ => bollard::errors::Error::IOError { err },
+            })?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| build_error(e.to_string()))?;
+        tokio::spawn(connection);
+
+        let (chunks, upload) = context.stream();
+        let body = StreamBody::new(futures::stream::unfold(chunks, |mut chunks| async move {
+            let chunk = chunks.recv().await?;
+            Some((
+                Ok::<_, std::convert::Infallible>(Frame::data(chunk)),
+                chunks,
+            ))
+        }));
+        let request = Request::post(format!("/build?{}", query))
+            .header(HOST, "docker")
+            .header(CONTENT_TYPE, "application/x-tar")
+            .body(body)
+            .map_err(|e| build_error(e.to_string()))?;
+
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(|e| build_error(e.to_string()))?;
+        let status = response.status();
+        let mut body = response.into_body();
+
+        let mut built = Ok(());
+        if status.is_success() {
+            // The daemon answers with one JSON message per line
+            let mut pending = Vec::new();
+            while let Some(frame) = body.frame().await {
+                let frame = frame.map_err(|e| build_error(e.to_string()))?;
+                if let Some(data) = frame.data_ref() {
+                    pending.extend_from_slice(data);
+                }
+                while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    if built.is_err() || line.trim_ascii().is_empty() {
+                        continue;
+                    }
+                    let info: BuildInfo =
+                        serde_json::from_slice(&line).map_err(|e| build_error(e.to_string()))?;
+                    built = handle_build_info(info, image_tag, output);
+                }
+            }
+        } else {
+            #[derive(serde::Deserialize)]
+            struct ErrorResponse {
+                message: String,
+            }
+            let bytes = body
+                .collect()
+                .await
+                .map_err(|e| build_error(e.to_string()))?
+                .to_bytes();
+            let message = serde_json::from_slice::<ErrorResponse>(&bytes)
+                .map(|e| e.message)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&bytes).trim().to_string());
+            built = Err(build_error(message));
+        }
+        drop(sender);
+
+        // A failed upload truncates the context, so its error explains the
+        // build failure better than Docker's; a closed pipe only means
+        // Docker stopped reading after failing on its own
+        match upload.await.map_err(|e| build_error(e.to_string()))? {
+            Err(VulnPkgError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            Err(e) => return Err(e),
+            Ok(()) => {}
+        }
+        built?;
+
+        output.success(&format!("Image built: {}", image_tag));
+        Ok(())
+    }
+
+    fn build_options(
+        image_tag: &str,
+        dockerfile_path: &str,
+        build: &BuildOptions,
+    ) -> BuildImageOptions<String> {
+        BuildImageOptions {
+            dockerfile: dockerfile_path.to_string(),
+            t: image_tag.to_string(),
             rm: true,
             forcerm: true,
+            nocache: build.no_cache,
+            buildargs: build.build_args.clone(),
+            labels: build.labels.clone(),
+            target: build.target.clone().unwrap_or_default(),
+            platform: build.platform.clone().unwrap_or_default(),
             ..Default::default()
-        };
+        }
+    }
 
+    /// Core build method using bollard
+    async fn build_image_from_tarball(
+        &self,
+        tar_bytes: Vec<u8>,
+        options: BuildImageOptions<String>,
+        output: &Output,
+    ) -> Result<()> {
+        let image_tag = options.t.clone();
         let mut stream = self
             .docker
-            .build_image(options, None, Some(tar_bytes.to_vec().into()));
+            .build_image(options, None, Some(tar_bytes.into()));
 
         while let Some(result) = stream.next().await {
-            match result {
-                Ok(info) => {
-                    if let Some(stream_text) = info.stream {
-                        let text = stream_text.trim();
-                        if !text.is_empty() {
-                            output.build_log(text);
-                        }
-                    }
-                    if let Some(error) = info.error {
-                        return Err(VulnPkgError::ImageBuild {
-                            image: image_tag.to_string(),
-                            message: error,
-                        });
-                    }
-                }
-                Err(e) => return Err(VulnPkgError::Docker(e)),
-            }
+            handle_build_info(result?, &image_tag, output)?;
         }
 
         output.success(&format!("Image built: {}", image_tag));
@@ -432,145 +779,189 @@ impl DockerManager {
         Ok(buf)
     }
 
-    /// Create a tarball from a directory (for Git repos)
-    fn create_context_tarball(dir: &Path, _dockerfile_path: &str) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        {
-            let encoder = GzEncoder::new(&mut buf, Compression::default());
-            let mut tar = Builder::new(encoder);
-
-            // Recursively add all files from the directory
-            tar.append_dir_all(".", dir)?;
-            tar.finish()?;
-        }
-        Ok(buf)
-    }
+    // ==================== Git Helpers ====================
 
-    /// Fetch remote context tarball and merge with Dockerfile
-    async fn fetch_and_merge_context(
-        dockerfile_content: &str,
-        context_url: &str,
-    ) -> Result<Vec<u8>> {
-        let response = reqwest::get(context_url)
-            .await
-            .map_err(|e| VulnPkgError::ContextFetch {
-                url: context_url.to_string(),
-                source: e,
-            })?;
+    /// Open the clone at `clone_dir` (initializing it on first use) and
+    /// fetch what is needed to check out `git_ref`
+    fn clone_or_open_repo(
+        repo_url: &str,
+        clone_dir: &Path,
+        git_ref: Option<&str>,
+        depth: Option<u32>,
+        credentials: &GitCredentials,
+    ) -> Result<Repository> {
+        let clone_error = |message: String| VulnPkgError::GitClone {
+            repo: repo_url.to_string(),
+            message,
+        };
 
-        let context_bytes = response
-            .bytes()
-            .await
-            .map_err(|e| VulnPkgError::ContextFetch {
-                url: context_url.to_string(),
-                source: e,
-            })?;
+        let repo = if clone_dir.exists() {
+            let repo = Repository::open(clone_dir).map_err(|e| clone_error(e.to_string()))?;
+            // The manifest may have moved the app to another repository
+            let current = repo
+                .find_remote("origin")
+                .ok()
+                .and_then(|remote| remote.url().map(str::to_string));
+            if current.as_deref() != Some(repo_url) {
+                repo.remote_set_url("origin", repo_url)
+                    .map_err(|e| clone_error(e.to_string()))?;
+            }
+            repo
+        } else {
+            let repo = Repository::init(clone_dir).map_err(|e| clone_error(e.to_string()))?;
+            repo.remote("origin", repo_url)
+                .map_err(|e| clone_error(e.to_string()))?;
+            repo
+        };
 
-        // For now, we'll extract the context and add/replace the Dockerfile
-        // This is a simplified implementation that assumes the context is a tar.gz
-        use flate2::read::GzDecoder;
-        use std::io::Read;
+        // libgit2 cannot fetch shallowly from a local path, and there is
+        // no download to save there anyway
+        let depth = depth.filter(|_| url_host(repo_url).is_some());
 
-        let decoder = GzDecoder::new(&context_bytes[..]);
-        let mut archive = tar::Archive::new(decoder);
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(credentials.remote_callbacks(repo_url));
+        if let Some(depth) = depth {
+            fetch_options.depth(depth.try_into().unwrap_or(i32::MAX));
+        }
+        // Shallow fetches only take the one ref they need; following every
+        // tag would pull in the history the depth is meant to skip
+        fetch_options.download_tags(if depth.is_some() {
+            AutotagOption::None
+        } else {
+            AutotagOption::All
+        });
 
-        // Create a new tarball with the context + our Dockerfile
-        let mut buf = Vec::new();
+        // Fetch latest from origin (scope to drop remote before returning repo)
         {
-            let encoder = GzEncoder::new(&mut buf, Compression::default());
-            let mut new_tar = Builder::new(encoder);
-
-            // Add all files from the original context except Dockerfile
-            for entry in archive.entries()? {
-                let mut entry = entry?;
-                let path = entry.path()?.into_owned();
-                if path.to_string_lossy() != "Dockerfile" {
-                    let mut header = entry.header().clone();
-                    let mut data = Vec::new();
-                    entry.read_to_end(&mut data)?;
-                    new_tar.append_data(&mut header, &path, &data[..])?;
-                }
-            }
-
-            // Add our Dockerfile
-            let dockerfile_bytes = dockerfile_content.as_bytes();
-            let mut header = tar::Header::new_gnu();
-            header.set_size(dockerfile_bytes.len() as u64);
-            header.set_mode(0o644);
-            header.set_cksum();
-            new_tar.append_data(&mut header, "Dockerfile", dockerfile_bytes)?;
-
-            new_tar.finish()?;
+            let mut remote = repo
+                .find_remote("origin")
+                .map_err(|e| clone_error(e.to_string()))?;
+
+            remote
+                .fetch(
+                    &fetch_refspecs(git_ref, depth),
+                    Some(&mut fetch_options),
+                    None,
+                )
+                .map_err(|e| clone_error(format!("Failed to fetch: {}", e)))?;
         }
 
-        Ok(buf)
+        Ok(repo)
     }
 
-    // ==================== Git Helpers ====================
+    fn checkout_ref(repo: &Repository, ref_name: &str) -> Result<()> {
+        let checkout_error = |message: String| VulnPkgError::GitCheckout {
+            ref_name: ref_name.to_string(),
+            message,
+        };
 
-    fn clone_or_open_repo(repo_url: &str, clone_dir: &Path) -> Result<Repository> {
-        if clone_dir.exists() {
-            // Open existing and fetch updates
-            let repo = Repository::open(clone_dir).map_err(|e| VulnPkgError::GitClone {
-                repo: repo_url.to_string(),
-                message: e.to_string(),
-            })?;
+        // Try to find the ref (could be branch, tag, or commit); remote
+        // branches first, since local ones are not updated by fetches
+        let commit = repo
+            .revparse_single(&format!("origin/{}", ref_name))
+            .or_else(|_| repo.revparse_single(ref_name))
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| checkout_error(e.to_string()))?;
 
-            // Fetch latest from origin (scope to drop remote before returning repo)
-            {
-                let mut remote =
-                    repo.find_remote("origin")
-                        .map_err(|e| VulnPkgError::GitClone {
-                            repo: repo_url.to_string(),
-                            message: e.to_string(),
-                        })?;
-
-                remote
-                    .fetch(&["refs/heads/*:refs/remotes/origin/*"], None, None)
-                    .map_err(|e| VulnPkgError::GitClone {
-                        repo: repo_url.to_string(),
-                        message: format!("Failed to fetch: {}", e),
-                    })?;
-            }
+        reset_worktree(repo, &commit).map_err(|e| checkout_error(e.to_string()))
+    }
 
-            Ok(repo)
-        } else {
-            // Fresh clone
-            Repository::clone(repo_url, clone_dir).map_err(|e| VulnPkgError::GitClone {
-                repo: repo_url.to_string(),
-                message: e.to_string(),
-            })
-        }
+    /// Commit `git_ref` (or the default branch) points to on the remote,
+    /// without cloning. A ref that is itself a commit SHA is returned as-is.
+    pub fn remote_commit(
+        repo_url: &str,
+        git_ref: Option<&str>,
+        credentials: &GitCredentials,
+    ) -> Result<Option<String>> {
+        let clone_error = |e: git2::Error| VulnPkgError::GitClone {
+            repo: repo_url.to_string(),
+            message: e.to_string(),
+        };
+
+        let mut remote = git2::Remote::create_detached(repo_url).map_err(clone_error)?;
+        let connection = remote
+            .connect_auth(
+                git2::Direction::Fetch,
+                Some(credentials.remote_callbacks(repo_url)),
+                None,
+            )
+            .map_err(clone_error)?;
+        let heads: Vec<(String, String)> = connection
+            .list()
+            .map_err(clone_error)?
+            .iter()
+            .map(|head| (head.name().to_string(), head.oid().to_string()))
+            .collect();
+
+        Ok(match_remote_ref(&heads, git_ref))
     }
 
-    fn checkout_ref(repo: &Repository, ref_name: &str) -> Result<()> {
-        // Try to find the ref (could be branch, tag, or commit)
-        let object = repo
-            .revparse_single(ref_name)
-            .or_else(|_| repo.revparse_single(&format!("origin/{}", ref_name)))
-            .map_err(|e| VulnPkgError::GitCheckout {
-                ref_name: ref_name.to_string(),
-                message: e.to_string(),
-            })?;
+    /// Check out every submodule at the commit recorded by its parent,
+    /// recursively
+    fn update_submodules(repo: &Repository, credentials: &GitCredentials) -> Result<()> {
+        let submodules = repo.submodules().map_err(|e| VulnPkgError::GitClone {
+            repo: repo.path().display().to_string(),
+            message: e.to_string(),
+        })?;
 
-        repo.checkout_tree(&object, None)
-            .map_err(|e| VulnPkgError::GitCheckout {
-                ref_name: ref_name.to_string(),
-                message: e.to_string(),
-            })?;
+        let parent_url = repo
+            .find_remote("origin")
+            .ok()
+            .and_then(|remote| remote.url().map(str::to_string));
+
+        for mut submodule in submodules {
+            let url = submodule.url().unwrap_or_default();
+            // Credentials are looked up by host, which relative URLs lack
+            let url = match &parent_url {
+                Some(parent_url) => resolve_submodule_url(parent_url, url),
+                None => url.to_string(),
+            };
+            let submodule_error = |e: git2::Error| VulnPkgError::GitClone {
+                repo: url.clone(),
+                message: format!("Failed to update submodule: {}", e),
+            };
 
-        // Set HEAD to point to the commit
-        repo.set_head_detached(object.id())
-            .map_err(|e| VulnPkgError::GitCheckout {
-                ref_name: ref_name.to_string(),
-                message: e.to_string(),
-            })?;
+            // Fetch from the resolved URL too, so the credentials match it
+            // (and libgit2 mangles relative paths under a file:// parent)
+            if let Some(name) = submodule.name() {
+                repo.config()
+                    .and_then(|mut config| config.set_str(&format!("submodule.{}.url", name), &url))
+                    .and_then(|()| submodule.reload(true))
+                    .map_err(submodule_error)?;
+            }
 
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.remote_callbacks(credentials.remote_callbacks(&url));
+            let mut options = SubmoduleUpdateOptions::new();
+            options.fetch(fetch_options);
+
+            submodule
+                .update(true, Some(&mut options))
+                .map_err(submodule_error)?;
+            let nested = submodule.open().map_err(submodule_error)?;
+            let commit = nested
+                .head()
+                .and_then(|head| head.peel_to_commit())
+                .map_err(submodule_error)?;
+            reset_worktree(&nested, &commit).map_err(submodule_error)?;
+            Self::update_submodules(&nested, credentials)?;
+        }
         Ok(())
     }
 
-    fn sanitize_repo_name(url: &str) -> String {
-        url.replace(['/', ':', '@', '.'], "_")
+    /// Resolve a normalized relative path inside a checkout, rejecting
+    /// symlinks that lead outside of it. Missing paths are returned as-is.
+    fn checkout_path(base: &Path, relative: &str, repo_url: &str) -> Result<PathBuf> {
+        let path = base.join(relative);
+        if let (Ok(resolved), Ok(root)) = (path.canonicalize(), base.canonicalize())
+            && !resolved.starts_with(&root)
+        {
+            return Err(VulnPkgError::ManifestValidation(format!(
+                "'{}' in repository '{}' links outside the repository",
+                relative, repo_url
+            )));
+        }
+        Ok(path)
     }
 
     // ==================== Container Management ====================
@@ -599,6 +990,10 @@ impl DockerManager {
         // Build Traefik labels for HTTP ports only
         let mut labels = HashMap::new();
         labels.insert(CONTAINER_LABEL.to_string(), app.name.clone());
+        labels.insert(
+            CONFIG_LABEL.to_string(),
+            container_config_hash(app, domain, https),
+        );
 
         let http_ports = app.http_ports();
         let has_http_ports = !http_ports.is_empty();
@@ -788,6 +1183,70 @@ impl DockerManager {
         Ok(result)
     }
 
+    /// List app containers with the metadata needed to rebuild their state
+    /// entries (hostnames from Traefik labels, host ports from port bindings)
+    pub async fn list_managed_containers(&self) -> Result<Vec<ManagedContainer>> {
+        let mut filters = HashMap::new();
+        filters.insert("label", vec![CONTAINER_LABEL]);
+
+        let options = ListContainersOptions {
+            all: true,
+            filters,
+            ..Default::default()
+        };
+
+        let containers = self.docker.list_containers(Some(options)).await?;
+
+        let result = containers
+            .into_iter()
+            .filter_map(|c| {
+                let id = c.id?;
+                let labels = c.labels.unwrap_or_default();
+                let app = labels.get(CONTAINER_LABEL)?.clone();
+                // Skip traefik container
+                if app == "traefik" {
+                    return None;
+                }
+
+                let mut allocated_ports: Vec<AllocatedPort> = Vec::new();
+                for port in c.ports.unwrap_or_default() {
+                    let protocol = match port.typ {
+                        Some(PortTypeEnum::TCP) => Protocol::Tcp,
+                        Some(PortTypeEnum::UDP) => Protocol::Udp,
+                        _ => continue,
+                    };
+                    let Some(host_port) = port.public_port else {
+                        continue;
+                    };
+                    // Docker reports IPv4 and IPv6 bindings separately
+                    let duplicate = allocated_ports
+                        .iter()
+                        .any(|p| p.container_port == port.private_port && p.protocol == protocol);
+                    if !duplicate {
+                        allocated_ports.push(AllocatedPort {
+                            container_port: port.private_port,
+                            host_port,
+                            protocol,
+                            label: None,
+                        });
+                    }
+                }
+                allocated_ports.sort_by_key(|p| p.container_port);
+
+                Some(ManagedContainer {
+                    id,
+                    hostnames: hostnames_from_labels(&app, &labels),
+                    app,
+                    running: c.state.as_deref() == Some("running"),
+                    image: c.image,
+                    allocated_ports,
+                })
+            })
+            .collect();
+
+        Ok(result)
+    }
+
     pub async fn container_running(&self, container_id: &str) -> Result<bool> {
         match self.docker.inspect_container(container_id, None).await {
             Ok(info) => Ok(info.state.and_then(|s| s.running).unwrap_or(false)),
@@ -798,6 +1257,24 @@ impl DockerManager {
         }
     }
 
+    /// Whether a container was created with the same image, env, ports,
+    /// domain and HTTPS setting that `create_container` would use now.
+    /// Containers from before the config label was added never match.
+    pub async fn container_matches(
+        &self,
+        container_id: &str,
+        app: &App,
+        domain: &str,
+        https: bool,
+    ) -> Result<bool> {
+        let info = self.docker.inspect_container(container_id, None).await?;
+        let recorded = info
+            .config
+            .and_then(|config| config.labels)
+            .and_then(|labels| labels.get(CONFIG_LABEL).cloned());
+        Ok(recorded.as_deref() == Some(container_config_hash(app, domain, https).as_str()))
+    }
+
     pub async fn count_running_apps(&self) -> Result<usize> {
         let containers = self.list_vuln_pkg_containers().await?;
         Ok(containers
@@ -837,3 +1314,566 @@ impl DockerManager {
         Ok(None)
     }
 }
+
+/// Recover the HTTP hostnames of an app from the Traefik labels written by
+/// `create_container`, primary hostname first
+fn hostnames_from_labels(app_name: &str, labels: &HashMap<String, String>) -> Vec<String> {
+    let mut routers: Vec<(bool, u16, String)> = labels
+        .iter()
+        .filter_map(|(key, value)| {
+            let router = key
+                .strip_prefix("traefik.http.routers.")?
+                .strip_suffix(".rule")?;
+
+            // HTTPS routers duplicate the hostname of their HTTP router
+            let entrypoint = labels.get(&format!("traefik.http.routers.{}.entrypoints", router));
+            if entrypoint.map(String::as_str) == Some("websecure") {
+                return None;
+            }
+
+            let hostname = value.strip_prefix("Host(`")?.strip_suffix("`)")?;
+            let port = labels
+                .get(&format!(
+                    "traefik.http.services.{}.loadbalancer.server.port",
+                    router
+                ))
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(0);
+            Some((router != app_name, port, hostname.to_string()))
+        })
+        .collect();
+
+    routers.sort();
+    routers
+        .into_iter()
+        .map(|(_, _, hostname)| hostname)
+        .collect()
+}
+
+/// Directory name of the clone used by `app` at `git_ref`: a readable,
+/// sanitized `<app>@<ref>` plus a short hash of the raw pair, since
+/// sanitizing alone maps `my.app` and `my_app` to the same name
+fn clone_dir_name(app: &str, git_ref: Option<&str>) -> String {
+    let git_ref = git_ref.unwrap_or("HEAD");
+    let sanitize = |s: &str| s.replace(['/', '\\', ':', '@', '.'], "_");
+    let digest = sha256_hex(format!("{}\0{}", app, git_ref).as_bytes());
+    format!("{}@{}-{}", sanitize(app), sanitize(git_ref), &digest[..8])
+}
+
+/// Resolve a submodule URL such as `../dep.git` against the parent
+/// repository's remote URL, the way git does. Absolute URLs are returned
+/// unchanged.
+fn resolve_submodule_url(parent_url: &str, url: &str) -> String {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return url.to_string();
+    }
+
+    let mut base = parent_url.trim_end_matches('/');
+    let mut rest = url;
+    loop {
+        if let Some(stripped) = rest.strip_prefix("./") {
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("../") {
+            rest = stripped;
+            // Drop the last path component, keeping its separator (`/`, or
+            // `:` for scp-like `git@host:repo.git` URLs)
+            if let Some(i) = base.trim_end_matches(['/', ':']).rfind(['/', ':']) {
+                base = &base[..=i];
+            }
+        } else {
+            break;
+        }
+    }
+
+    if base.ends_with(['/', ':']) {
+        format!("{}{}", base, rest)
+    } else {
+        format!("{}/{}", base, rest)
+    }
+}
+
+/// Detach HEAD at `commit` and make the working tree match it exactly:
+/// local modifications are reverted and untracked or ignored files
+/// (including build leftovers) are deleted
+fn reset_worktree(
+    repo: &Repository,
+    commit: &git2::Commit,
+) -> std::result::Result<(), git2::Error> {
+    repo.set_head_detached(commit.id())?;
+    repo.reset(commit.as_object(), git2::ResetType::Hard, None)?;
+
+    let mut options = git2::StatusOptions::new();
+    options
+        .include_untracked(true)
+        .include_ignored(true)
+        .exclude_submodules(true);
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("repository has no working tree"))?;
+
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let status = entry.status();
+        if !(status.is_wt_new() || status.is_ignored()) {
+            continue;
+        }
+        let Some(path) = entry.path() else { continue };
+        let path = workdir.join(path);
+        let removed = if path.is_dir() && !path.is_symlink() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        removed.map_err(|e| {
+            git2::Error::from_str(&format!("failed to remove {}: {}", path.display(), e))
+        })?;
+    }
+    Ok(())
+}
+
+/// Download `url`, verifying it against `sha256` when the manifest pins one.
+/// Verified downloads are kept in `cache_dir` and reused by later builds
+/// of the same URL and digest; unpinned ones are fetched every time.
+async fn fetch_artifact(
+    url: &str,
+    sha256: Option<&str>,
+    cache_dir: &Path,
+    fetch_error: fn(String, reqwest::Error) -> VulnPkgError,
+    output: &Output,
+) -> Result<Vec<u8>> {
+    let expected = sha256.map(str::to_lowercase);
+    let cached = expected
+        .as_ref()
+        .map(|digest| cache_dir.join(artifact_cache_name(url, digest)));
+
+    if let (Some(path), Some(digest)) = (&cached, &expected)
+        && let Some(data) = read_verified(path, digest)
+    {
+        output.info(&format!("Using verified copy of {}", url));
+        return Ok(data);
+    }
+
+    let data = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| fetch_error(url.to_string(), e))?
+        .bytes()
+        .await
+        .map_err(|e| fetch_error(url.to_string(), e))?
+        .to_vec();
+
+    let (Some(path), Some(digest)) = (cached, expected) else {
+        return Ok(data);
+    };
+    let found = sha256_hex(&data);
+    if found != digest {
+        return Err(VulnPkgError::ChecksumMismatch {
+            url: url.to_string(),
+            expected: digest,
+            found,
+        });
+    }
+    output.info(&format!("Verified sha256 {}", digest));
+
+    // Write then rename, so an interrupted write never leaves a bad entry
+    std::fs::create_dir_all(cache_dir)?;
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, &data)?;
+    std::fs::rename(&partial, &path)?;
+    Ok(data)
+}
+
+/// Contents of the cached download at `path`, if it still has the expected
+/// digest. A missing or corrupted copy returns None and is downloaded again.
+fn read_verified(path: &Path, digest: &str) -> Option<Vec<u8>> {
+    std::fs::read(path)
+        .ok()
+        .filter(|data| sha256_hex(data) == digest)
+}
+
+/// Cache file name of a verified download. Keyed by URL as well as digest,
+/// so a digest copied to the wrong field fails like it would on a machine
+/// without the cache.
+fn artifact_cache_name(url: &str, digest: &str) -> String {
+    format!("{}-{}", &sha256_hex(url.as_bytes())[..16], digest)
+}
+
+/// Lowercase hex SHA-256 of `data`
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Hash of everything `create_container` bakes into a container, so a
+/// stopped container can be checked before it is reused
+fn container_config_hash(app: &App, domain: &str, https: bool) -> String {
+    let config = serde_json::json!({
+        "image": app.effective_image(),
+        "env": app.env,
+        "ports": app.port_configs(),
+        "domain": domain,
+        "https": https,
+    });
+    sha256_hex(config.to_string().as_bytes())
+}
+
+/// Whether the full SHA `found` is the (possibly abbreviated) commit `expected`
+pub fn commit_matches(found: &str, expected: &str) -> bool {
+    !expected.is_empty()
+        && found
+            .get(..expected.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(expected))
+}
+
+/// Resolve `git_ref` against an advertised ref list the way `checkout_ref`
+/// resolves it in a clone: branches first, then (peeled) tags
+fn match_remote_ref(heads: &[(String, String)], git_ref: Option<&str>) -> Option<String> {
+    let find = |name: &str| {
+        heads
+            .iter()
+            .find(|(head, _)| head == name)
+            .map(|(_, oid)| oid.clone())
+    };
+
+    let Some(name) = git_ref else {
+        return find("HEAD");
+    };
+    find(&format!("refs/heads/{}", name))
+        .or_else(|| find(&format!("refs/tags/{}^{{}}", name)))
+        .or_else(|| find(&format!("refs/tags/{}", name)))
+        .or_else(|| find(name))
+        .or_else(|| is_commit_sha(name).then(|| name.to_string()))
+}
+
+/// The `repo@sha256:...` entry of `repo_digests` belonging to `image`
+fn repo_digest(image: &str, repo_digests: &[String]) -> Option<String> {
+    let repository = image.split('@').next().unwrap_or(image);
+    // Strip a tag, but not a registry port (`host:5000/app`)
+    let repository = match repository.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => repository,
+    };
+
+    let digests: Vec<(&str, &str)> = repo_digests
+        .iter()
+        .filter_map(|entry| entry.split_once('@'))
+        .collect();
+    digests
+        .iter()
+        .find(|(name, _)| *name == repository)
+        .or(digests.first())
+        .map(|(_, digest)| digest.to_string())
+}
+
+/// Refspecs for fetching `git_ref`. Full fetches take every branch and tag
+/// plus the remote HEAD; shallow ones only the branch or tag named `git_ref`
+/// (or the remote HEAD when no ref is given).
+fn fetch_refspecs(git_ref: Option<&str>, depth: Option<u32>) -> Vec<String> {
+    const HEAD: &str = "+HEAD:refs/remotes/origin/HEAD";
+
+    match (git_ref, depth) {
+        (_, None) => vec![
+            HEAD.to_string(),
+            "+refs/heads/*:refs/remotes/origin/*".to_string(),
+            "+refs/tags/*:refs/tags/*".to_string(),
+        ],
+        (None, Some(_)) => vec![HEAD.to_string()],
+        (Some(name), Some(_)) if name.starts_with("refs/") => vec![format!("+{0}:{0}", name)],
+        (Some(name), Some(_)) => vec![
+            format!("+refs/heads/{0}:refs/remotes/origin/{0}", name),
+            format!("+refs/tags/{0}:refs/tags/{0}", name),
+        ],
+    }
+}
+
+/// Report one message of a build's output stream
+fn handle_build_info(info: BuildInfo, image_tag: &str, output: &Output) -> Result<()> {
+    if let Some(stream_text) = info.stream {
+        let text = stream_text.trim();
+        if !text.is_empty() {
+            if let Some((step, steps)) = parse_build_step(text) {
+                output.progress(Progress {
+                    action: "build",
+                    image: image_tag.to_string(),
+                    layer: None,
+                    status: text.to_string(),
+                    current: Some(step),
+                    total: Some(steps),
+                });
+            }
+            output.build_log(text);
+        }
+    }
+    if let Some(error) = info.error {
+        return Err(VulnPkgError::ImageBuild {
+            image: image_tag.to_string(),
+            message: error,
+        });
+    }
+    Ok(())
+}
+
+/// Local Docker socket the daemon is reached through, if any
+#[cfg(unix)]
+fn docker_socket() -> Option<std::path::PathBuf> {
+    match std::env::var("DOCKER_HOST") {
+        Ok(host) => host.strip_prefix("unix://").map(Into::into),
+        Err(_) => Some("/var/run/docker.sock".into()),
+    }
+}
+
+/// Parse the classic builder's `Step 3/10 : RUN ...` line into (step, total)
+fn parse_build_step(text: &str) -> Option<(i64, i64)> {
+    let (counter, _) = text.strip_prefix("Step ")?.split_once(' ')?;
+    let (step, total) = counter.split_once('/')?;
+    Some((step.parse().ok()?, total.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_hostnames_from_labels() {
+        let labels: HashMap<String, String> = [
+            label("vuln-pkg", "webgoat"),
+            label(
+                "traefik.http.routers.webgoat-9090.rule",
+                "Host(`webgoat-9090.lab.local`)",
+            ),
+            label("traefik.http.routers.webgoat-9090.entrypoints", "web"),
+            label(
+                "traefik.http.services.webgoat-9090.loadbalancer.server.port",
+                "9090",
+            ),
+            label(
+                "traefik.http.routers.webgoat.rule",
+                "Host(`webgoat.lab.local`)",
+            ),
+            label("traefik.http.routers.webgoat.entrypoints", "web"),
+            label(
+                "traefik.http.services.webgoat.loadbalancer.server.port",
+                "8080",
+            ),
+            label(
+                "traefik.http.routers.webgoat-secure.rule",
+                "Host(`webgoat.lab.local`)",
+            ),
+            label(
+                "traefik.http.routers.webgoat-secure.entrypoints",
+                "websecure",
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            hostnames_from_labels("webgoat", &labels),
+            vec!["webgoat.lab.local", "webgoat-9090.lab.local"]
+        );
+    }
+
+    #[test]
+    fn test_hostnames_from_labels_without_http_ports() {
+        let labels: HashMap<String, String> = [label("vuln-pkg", "mongo")].into_iter().collect();
+        assert!(hostnames_from_labels("mongo", &labels).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_submodule_url() {
+        let parent = "https://git.example.com/org/app.git";
+        assert_eq!(
+            resolve_submodule_url(parent, "../dep.git"),
+            "https://git.example.com/org/dep.git"
+        );
+        assert_eq!(
+            resolve_submodule_url(parent, "../../other/dep.git"),
+            "https://git.example.com/other/dep.git"
+        );
+        assert_eq!(
+            resolve_submodule_url(parent, "./dep.git"),
+            "https://git.example.com/org/app.git/dep.git"
+        );
+        assert_eq!(
+            resolve_submodule_url("git@git.example.com:app.git", "../dep.git"),
+            "git@git.example.com:dep.git"
+        );
+        assert_eq!(
+            resolve_submodule_url(parent, "https://github.com/x/y.git"),
+            "https://github.com/x/y.git"
+        );
+        // The host is what credentials are matched on
+        assert_eq!(
+            url_host(&resolve_submodule_url(parent, "../dep.git")).as_deref(),
+            Some("git.example.com")
+        );
+    }
+
+    #[test]
+    fn test_clone_dir_name() {
+        let name = clone_dir_name("struts2", Some("feature/x"));
+        assert!(name.starts_with("struts2@feature_x-"), "{}", name);
+        assert_eq!(name, clone_dir_name("struts2", Some("feature/x")));
+        assert!(clone_dir_name("struts2", None).starts_with("struts2@HEAD-"));
+
+        // Names that sanitize the same still get their own clone
+        assert_ne!(
+            clone_dir_name("my.app", Some("main")),
+            clone_dir_name("my_app", Some("main"))
+        );
+        assert_ne!(
+            clone_dir_name("lab", Some("release/1.0")),
+            clone_dir_name("lab", Some("release_1.0"))
+        );
+    }
+
+    #[test]
+    fn test_match_remote_ref() {
+        let heads: Vec<(String, String)> = [
+            ("HEAD", "aaaaaaa"),
+            ("refs/heads/main", "aaaaaaa"),
+            ("refs/heads/dev", "bbbbbbb"),
+            ("refs/tags/v1", "ccccccc"),
+            ("refs/tags/v2", "ddddddd"),
+            ("refs/tags/v2^{}", "eeeeeee"),
+        ]
+        .iter()
+        .map(|(name, oid)| (name.to_string(), oid.to_string()))
+        .collect();
+
+        assert_eq!(match_remote_ref(&heads, None).as_deref(), Some("aaaaaaa"));
+        assert_eq!(
+            match_remote_ref(&heads, Some("dev")).as_deref(),
+            Some("bbbbbbb")
+        );
+        assert_eq!(
+            match_remote_ref(&heads, Some("v1")).as_deref(),
+            Some("ccccccc")
+        );
+        // Annotated tags resolve to the commit, not the tag object
+        assert_eq!(
+            match_remote_ref(&heads, Some("v2")).as_deref(),
+            Some("eeeeeee")
+        );
+        assert_eq!(
+            match_remote_ref(&heads, Some("refs/heads/dev")).as_deref(),
+            Some("bbbbbbb")
+        );
+        assert_eq!(
+            match_remote_ref(&heads, Some("9fceb02")).as_deref(),
+            Some("9fceb02")
+        );
+        assert_eq!(match_remote_ref(&heads, Some("gone")), None);
+    }
+
+    #[test]
+    fn test_repo_digest() {
+        let digests = vec![
+            "mirror.local/web-dvwa@sha256:1111".to_string(),
+            "vulnerables/web-dvwa@sha256:2222".to_string(),
+        ];
+        assert_eq!(
+            repo_digest("vulnerables/web-dvwa:latest", &digests).as_deref(),
+            Some("sha256:2222")
+        );
+        assert_eq!(
+            repo_digest(
+                "localhost:5000/app",
+                &["localhost:5000/app@sha256:3333".to_string()]
+            )
+            .as_deref(),
+            Some("sha256:3333")
+        );
+        assert_eq!(repo_digest("vuln-pkg/app:1.0", &[]), None);
+        assert!(commit_matches("9fceb02d0ae5", "9FCEB02"));
+        assert!(!commit_matches("9fceb02d0ae5", "9fceb03"));
+        assert!(!commit_matches("9fc", "9fceb02"));
+    }
+
+    #[test]
+    fn test_container_config_hash() {
+        let app = App {
+            name: "dvwa".to_string(),
+            image: Some("vulnerables/web-dvwa".to_string()),
+            ports: vec![crate::manifest::PortEntry::Simple(80)],
+            ..Default::default()
+        };
+        let hash = container_config_hash(&app, "localhost", false);
+        assert_eq!(
+            hash,
+            container_config_hash(&app.clone(), "localhost", false)
+        );
+
+        // Anything baked into the container changes the hash
+        let mut changed = app.clone();
+        changed.env.push("PHP_DEBUG=1".to_string());
+        assert_ne!(hash, container_config_hash(&changed, "localhost", false));
+        let mut changed = app.clone();
+        changed.ports = vec![crate::manifest::PortEntry::Simple(8080)];
+        assert_ne!(hash, container_config_hash(&changed, "localhost", false));
+        assert_ne!(hash, container_config_hash(&app, "lab.local", false));
+        assert_ne!(hash, container_config_hash(&app, "localhost", true));
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_read_verified() {
+        let dir = std::env::temp_dir().join(format!("vuln-pkg-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = b"FROM alpine\n";
+        let digest = sha256_hex(data);
+        let cached = dir.join(artifact_cache_name(
+            "https://example.com/Dockerfile",
+            &digest,
+        ));
+
+        let missing = read_verified(&cached, &digest);
+        std::fs::write(&cached, data).unwrap();
+        let verified = read_verified(&cached, &digest);
+        // A corrupted copy is not trusted
+        std::fs::write(&cached, b"FROM evil\n").unwrap();
+        let corrupted = read_verified(&cached, &digest);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(missing, None);
+        assert_eq!(verified.as_deref(), Some(&data[..]));
+        assert_eq!(corrupted, None);
+    }
+
+    #[test]
+    fn test_fetch_refspecs() {
+        assert_eq!(fetch_refspecs(Some("v1.0"), None).len(), 3);
+        assert_eq!(
+            fetch_refspecs(Some("v1.0"), Some(1)),
+            vec![
+                "+refs/heads/v1.0:refs/remotes/origin/v1.0",
+                "+refs/tags/v1.0:refs/tags/v1.0"
+            ]
+        );
+        assert_eq!(
+            fetch_refspecs(Some("refs/tags/v1.0"), Some(1)),
+            vec!["+refs/tags/v1.0:refs/tags/v1.0"]
+        );
+        assert_eq!(
+            fetch_refspecs(None, Some(1)),
+            vec!["+HEAD:refs/remotes/origin/HEAD"]
+        );
+    }
+
+    #[test]
+    fn test_parse_build_step() {
+        assert_eq!(parse_build_step("Step 3/10 : RUN make"), Some((3, 10)));
+        assert_eq!(parse_build_step("Step 1/1 : FROM alpine"), Some((1, 1)));
+        assert_eq!(parse_build_step(" ---> Running in 1234"), None);
+        assert_eq!(parse_build_step("Step x/2 : RUN"), None);
+    }
+}
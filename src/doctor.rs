@@ -0,0 +1,373 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::docker::DockerManager;
+use crate::reconcile;
+use crate::state::State;
+
+/// How long to wait on DNS lookups and the Traefik API before giving up
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of a single diagnostic check
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+    Skip,
+}
+
+/// A single diagnostic check with an actionable fix when it did not pass
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn skip(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skip,
+            message: message.into(),
+            fix: None,
+        }
+    }
+}
+
+/// Settings the checks are evaluated against (taken from the global CLI flags)
+pub struct DoctorContext<'a> {
+    pub domain: &'a str,
+    pub resolve_address: Ipv4Addr,
+    pub https: bool,
+}
+
+/// Run every environment check, in the order they should be read
+pub async fn run_checks(state: &State, ctx: &DoctorContext<'_>) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let docker = match DockerManager::new() {
+        Ok(docker) => docker,
+        Err(e) => {
+            checks.push(docker_unavailable(&e.to_string()));
+            skip_docker_checks(&mut checks);
+            checks.push(check_dns(state, ctx).await);
+            return checks;
+        }
+    };
+
+    match docker.server_version().await {
+        Ok(version) => checks.push(Check::ok("docker", format!("Docker {} reachable", version))),
+        Err(e) => {
+            checks.push(docker_unavailable(&e.to_string()));
+            skip_docker_checks(&mut checks);
+            checks.push(check_dns(state, ctx).await);
+            return checks;
+        }
+    }
+
+    let traefik = docker.find_traefik_container().await.ok().flatten();
+    let traefik_running = matches!(traefik, Some((_, true)));
+
+    checks.push(check_host_port(80, traefik_running));
+    if ctx.https {
+        checks.push(check_host_port(443, traefik_running));
+    }
+
+    checks.push(check_dns(state, ctx).await);
+
+    checks.push(match docker.network_exists().await {
+        Ok(true) => Check::ok("network", "Network 'vuln-pkg' exists"),
+        Ok(false) if state.apps.is_empty() => {
+            Check::ok("network", "Not present (created on demand)")
+        }
+        Ok(false) => Check::warn(
+            "network",
+            "Network 'vuln-pkg' does not exist",
+            "Run `vuln-pkg proxy start` to recreate it",
+        ),
+        Err(e) => Check::fail(
+            "network",
+            format!("Could not list Docker networks: {}", e),
+            "Check that your user can access the Docker socket",
+        ),
+    });
+
+    checks.push(check_traefik(state, ctx, traefik).await);
+
+    checks.push(match docker.list_managed_containers().await {
+        Ok(containers) => {
+            let mut preview = state.clone();
+            let report = reconcile::reconcile(&mut preview, &containers);
+            if report.is_clean() {
+                Check::ok("state", "state.json matches Docker containers")
+            } else {
+                let mut problems = Vec::new();
+                if !report.adopted.is_empty() {
+                    problems.push(format!("orphaned: {}", report.adopted.join(", ")));
+                }
+                if !report.relinked.is_empty() {
+                    problems.push(format!("recreated: {}", report.relinked.join(", ")));
+                }
+                if !report.pruned.is_empty() {
+                    problems.push(format!("missing: {}", report.pruned.join(", ")));
+                }
+                Check::warn(
+                    "state",
+                    format!("state.json is out of sync ({})", problems.join("; ")),
+                    "Run `vuln-pkg doctor --fix` (or `vuln-pkg adopt`)",
+                )
+            }
+        }
+        Err(e) => Check::fail(
+            "state",
+            format!("Could not list vuln-pkg containers: {}", e),
+            "Check that your user can access the Docker socket",
+        ),
+    });
+
+    checks
+}
+
+fn docker_unavailable(error: &str) -> Check {
+    Check::fail(
+        "docker",
+        format!("Cannot connect to Docker: {}", error),
+        "Start the Docker daemon (e.g. `sudo systemctl start docker` or Docker Desktop) \
+         and make sure your user can access the Docker socket",
+    )
+}
+
+fn skip_docker_checks(checks: &mut Vec<Check>) {
+    for name in ["ports", "network", "traefik", "state"] {
+        checks.push(Check::skip(name, "Skipped: Docker is not reachable"));
+    }
+}
+
+/// Probe whether a host port is free for Traefik to bind
+fn check_host_port(port: u16, traefik_running: bool) -> Check {
+    if traefik_running {
+        return Check::ok(
+            "ports",
+            format!("Port {} is held by vuln-pkg-traefik", port),
+        );
+    }
+
+    match TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))) {
+        Ok(_) => Check::ok("ports", format!("Port {} is available", port)),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => Check::fail(
+            "ports",
+            format!("Port {} is already in use by another process", port),
+            format!(
+                "Stop the service listening on port {} (find it with `sudo ss -ltnp 'sport = :{}'`)",
+                port, port
+            ),
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Check::skip(
+            "ports",
+            format!(
+                "Cannot probe port {} without elevated privileges (Docker binds it for us)",
+                port
+            ),
+        ),
+        Err(e) => Check::warn(
+            "ports",
+            format!("Could not probe port {}: {}", port, e),
+            "Make sure nothing else is listening on this port",
+        ),
+    }
+}
+
+/// Check that app hostnames resolve to `--resolve-address`
+async fn check_dns(state: &State, ctx: &DoctorContext<'_>) -> Check {
+    let hostname = probe_hostname(state, ctx.domain);
+    let lookup = tokio::time::timeout(
+        PROBE_TIMEOUT,
+        tokio::net::lookup_host((hostname.as_str(), 80)),
+    );
+
+    let uses_sslip = ctx.domain.ends_with(".sslip.io");
+    let fix = if uses_sslip {
+        "Your DNS resolver may block sslip.io answers (DNS rebinding protection). \
+         Allowlist sslip.io on your router, or use --domain with `vuln-pkg hosts sync` \
+         or `vuln-pkg dns`"
+            .to_string()
+    } else {
+        format!(
+            "Point *.{} at {} (`vuln-pkg hosts sync` or `vuln-pkg dns`)",
+            ctx.domain, ctx.resolve_address
+        )
+    };
+
+    match lookup.await {
+        Ok(Ok(addrs)) => {
+            let resolved: Vec<IpAddr> = addrs.map(|a| a.ip()).collect();
+            if resolved.contains(&IpAddr::V4(ctx.resolve_address)) {
+                Check::ok(
+                    "dns",
+                    format!("{} resolves to {}", hostname, ctx.resolve_address),
+                )
+            } else {
+                let found: Vec<String> = resolved.iter().map(|ip| ip.to_string()).collect();
+                Check::fail(
+                    "dns",
+                    format!(
+                        "{} resolves to {} instead of {}",
+                        hostname,
+                        found.join(", "),
+                        ctx.resolve_address
+                    ),
+                    fix,
+                )
+            }
+        }
+        Ok(Err(e)) => Check::fail("dns", format!("{} does not resolve: {}", hostname, e), fix),
+        Err(_) => Check::fail("dns", format!("Timed out resolving {}", hostname), fix),
+    }
+}
+
+/// Prefer a hostname that is actually in use; fall back to the dashboard
+fn probe_hostname(state: &State, domain: &str) -> String {
+    let suffix = format!(".{}", domain);
+    state
+        .apps
+        .values()
+        .flat_map(|app| app.hostnames.iter())
+        .find(|h| h.ends_with(&suffix))
+        .cloned()
+        .unwrap_or_else(|| format!("traefik.{}", domain))
+}
+
+async fn check_traefik(
+    state: &State,
+    ctx: &DoctorContext<'_>,
+    traefik: Option<(String, bool)>,
+) -> Check {
+    let http_apps_running = state
+        .apps
+        .values()
+        .any(|app| app.running && !app.hostnames.is_empty());
+
+    match traefik {
+        None if http_apps_running => Check::fail(
+            "traefik",
+            "HTTP apps are running but vuln-pkg-traefik is not",
+            "Run `vuln-pkg proxy start`",
+        ),
+        None => Check::ok("traefik", "Not running (started on demand)"),
+        Some((_, false)) => Check::warn(
+            "traefik",
+            "A stopped vuln-pkg-traefik container is left over",
+            "Run `vuln-pkg doctor --fix` to remove it",
+        ),
+        Some((_, true)) => match probe_traefik_api(ctx).await {
+            Ok(status) if status.is_success() => {
+                Check::ok("traefik", "vuln-pkg-traefik is running and answering")
+            }
+            Ok(status) => Check::warn(
+                "traefik",
+                format!("vuln-pkg-traefik answered with HTTP {}", status),
+                "Run `vuln-pkg proxy restart`",
+            ),
+            Err(e) => Check::fail(
+                "traefik",
+                format!(
+                    "vuln-pkg-traefik is running but not reachable at {}: {}",
+                    ctx.resolve_address, e
+                ),
+                "Check that --resolve-address points at this machine and that a \
+                 firewall is not blocking port 80",
+            ),
+        },
+    }
+}
+
+/// Hit the Traefik API through the dashboard router, the same path a browser takes
+async fn probe_traefik_api(
+    ctx: &DoctorContext<'_>,
+) -> std::result::Result<reqwest::StatusCode, reqwest::Error> {
+    let client = reqwest::Client::builder().timeout(PROBE_TIMEOUT).build()?;
+    let response = client
+        .get(format!("http://{}/api/version", ctx.resolve_address))
+        .header("Host", format!("traefik.{}", ctx.domain))
+        .send()
+        .await?;
+    Ok(response.status())
+}
+
+/// True when no check failed outright
+pub fn is_healthy(checks: &[Check]) -> bool {
+    checks.iter().all(|c| c.status != CheckStatus::Fail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+
+    #[test]
+    fn test_probe_hostname_prefers_app_hostnames() {
+        let mut state = State::new();
+        assert_eq!(probe_hostname(&state, "lab.local"), "traefik.lab.local");
+
+        state.apps.insert(
+            "dvwa".to_string(),
+            AppState {
+                hostnames: vec!["dvwa.lab.local".to_string()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(probe_hostname(&state, "lab.local"), "dvwa.lab.local");
+        assert_eq!(
+            probe_hostname(&state, "127.0.0.1.sslip.io"),
+            "traefik.127.0.0.1.sslip.io"
+        );
+    }
+
+    #[test]
+    fn test_warnings_do_not_make_unhealthy() {
+        let checks = vec![
+            Check::ok("docker", "ok"),
+            Check::warn("network", "missing", "run"),
+            Check::skip("ports", "skipped"),
+        ];
+        assert!(is_healthy(&checks));
+
+        let mut checks = checks;
+        checks.push(Check::fail("dns", "broken", "fix"));
+        assert!(!is_healthy(&checks));
+    }
+}
@@ -24,6 +24,12 @@ pub enum VulnPkgError {
     #[error("Application '{0}' is not running")]
     AppNotRunning(String),
 
+    #[error("Application '{app}' has no running endpoint for port '{port}'")]
+    PortNotFound { app: String, port: String },
+
+    #[error("Application '{0}' is installed from a manifest; choose another name with --name")]
+    AppNameTaken(String),
+
     #[error("Application '{0}' is a prebuilt package and cannot be rebuilt")]
     AppNotRebuildable(String),
 
@@ -33,6 +39,9 @@ pub enum VulnPkgError {
     #[error("Manifest validation error: {0}")]
     ManifestValidation(String),
 
+    #[error("Failed to parse lab file: {0}")]
+    LabParse(String),
+
     #[error("Failed to fetch Dockerfile from {url}: {source}")]
     DockerfileFetch {
         url: String,
@@ -47,6 +56,9 @@ pub enum VulnPkgError {
         source: reqwest::Error,
     },
 
+    #[error("Invalid build context from {url}: {message}")]
+    ContextArchive { url: String, message: String },
+
     #[error("Failed to build image '{image}': {message}")]
     ImageBuild { image: String, message: String },
 
@@ -56,14 +68,271 @@ pub enum VulnPkgError {
     #[error("Failed to checkout ref '{ref_name}': {message}")]
     GitCheckout { ref_name: String, message: String },
 
+    #[error("Dockerfile '{path}' not found in '{repo}'")]
+    DockerfileNotFound { repo: String, path: String },
+
+    #[error("Repository '{repo}' checked out commit {found}, but the manifest pins {expected}")]
+    CommitMismatch {
+        repo: String,
+        expected: String,
+        found: String,
+    },
+
+    #[error("Checksum mismatch for {url}: expected sha256 {expected}, got {found}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        found: String,
+    },
+
     #[error("Docker error: {0}")]
     Docker(#[from] bollard::errors::Error),
 
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[error(
+        "Failed to update {path}: {source} (re-run with sudo, or paste the output of `vuln-pkg hosts print` into it)"
+    )]
+    HostsFile {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Refusing to update {path}: {message}")]
+    HostsBlock { path: String, message: String },
+
+    #[error("Invalid credentials file {path}: {message}")]
+    Credentials { path: String, message: String },
+
+    #[error("DNS server error: {0}")]
+    DnsServer(String),
+
+    #[error("{failed} of {total} application(s) failed")]
+    BulkFailed { failed: usize, total: usize },
+
+    #[error("API server error: {0}")]
+    ApiServer(String),
+
+    #[error("{0} doctor check(s) failed")]
+    DoctorFailed(usize),
+
     #[error("State error: {0}")]
     State(String),
+
+    #[error(
+        "{file} was written by a newer vuln-pkg (schema v{found}, this version supports up to v{supported}); upgrade vuln-pkg before modifying it"
+    )]
+    StateVersion {
+        file: String,
+        found: u32,
+        supported: u32,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, VulnPkgError>;
+
+/// Stable process exit status, machine-readable code and description for
+/// every kind of error. Never renumber an entry; only append new ones.
+pub const EXIT_CODES: &[(i32, &str, &str)] = &[
+    (1, "internal", "Unexpected error"),
+    (10, "manifest_fetch", "The manifest could not be downloaded"),
+    (11, "manifest_parse", "The manifest is not valid YAML"),
+    (12, "manifest_invalid", "The manifest failed validation"),
+    (13, "manifest_rejected", "The manifest was not accepted"),
+    (20, "app_not_found", "The app is not in the manifest"),
+    (21, "app_not_installed", "The app is not installed"),
+    (22, "app_already_running", "The app is already running"),
+    (23, "app_not_running", "The app is not running"),
+    (24, "app_not_rebuildable", "Prebuilt apps cannot be rebuilt"),
+    (
+        25,
+        "port_not_found",
+        "The app has no endpoint for the requested port",
+    ),
+    (
+        26,
+        "app_name_taken",
+        "An app installed from a manifest already uses the name",
+    ),
+    (
+        30,
+        "docker_unavailable",
+        "Docker is not running or not reachable",
+    ),
+    (31, "docker", "Docker rejected a request"),
+    (32, "image_build", "Building the image failed"),
+    (
+        40,
+        "download_failed",
+        "A Dockerfile or build context could not be downloaded",
+    ),
+    (41, "git_clone", "Cloning the repository failed"),
+    (42, "git_checkout", "Checking out the git ref failed"),
+    (
+        43,
+        "dockerfile_not_found",
+        "The Dockerfile is missing from the repository",
+    ),
+    (
+        44,
+        "commit_mismatch",
+        "The checked-out commit does not match the manifest pin",
+    ),
+    (
+        45,
+        "checksum_mismatch",
+        "A downloaded Dockerfile or build context failed its checksum",
+    ),
+    (
+        46,
+        "context_invalid",
+        "The build context archive is unreadable or unsafe",
+    ),
+    (50, "io", "A file could not be read or written"),
+    (51, "state", "vuln-pkg state is unreadable"),
+    (52, "state_version", "State was written by a newer vuln-pkg"),
+    (53, "hosts_file", "The hosts file could not be updated"),
+    (54, "credentials_invalid", "credentials.yml is invalid"),
+    (60, "lab_invalid", "The lab file is invalid"),
+    (61, "dns_server", "The DNS server failed"),
+    (62, "api_server", "The API server failed"),
+    (
+        70,
+        "partial_failure",
+        "Some apps of a bulk operation failed",
+    ),
+    (71, "doctor_failed", "A doctor check failed"),
+];
+
+impl VulnPkgError {
+    /// Machine-readable error code (see [`EXIT_CODES`])
+    pub fn code(&self) -> &'static str {
+        match self {
+            VulnPkgError::ManifestFetch { .. } => "manifest_fetch",
+            VulnPkgError::ManifestParse(_) => "manifest_parse",
+            VulnPkgError::ManifestValidation(_) => "manifest_invalid",
+            VulnPkgError::ManifestRejected => "manifest_rejected",
+            VulnPkgError::AppNotFound(_) => "app_not_found",
+            VulnPkgError::AppNotInstalled(_) => "app_not_installed",
+            VulnPkgError::AppAlreadyRunning(_) => "app_already_running",
+            VulnPkgError::AppNotRunning(_) => "app_not_running",
+            VulnPkgError::AppNotRebuildable(_) => "app_not_rebuildable",
+            VulnPkgError::PortNotFound { .. } => "port_not_found",
+            VulnPkgError::AppNameTaken(_) => "app_name_taken",
+            VulnPkgError::Docker(e) if is_connection_error(e) => "docker_unavailable",
+            VulnPkgError::Docker(_) => "docker",
+            VulnPkgError::ImageBuild { .. } => "image_build",
+            VulnPkgError::DockerfileFetch { .. } | VulnPkgError::ContextFetch { .. } => {
+                "download_failed"
+            }
+            VulnPkgError::GitClone { .. } => "git_clone",
+            VulnPkgError::GitCheckout { .. } => "git_checkout",
+            VulnPkgError::DockerfileNotFound { .. } => "dockerfile_not_found",
+            VulnPkgError::CommitMismatch { .. } => "commit_mismatch",
+            VulnPkgError::ChecksumMismatch { .. } => "checksum_mismatch",
+            VulnPkgError::ContextArchive { .. } => "context_invalid",
+            VulnPkgError::Io(_) => "io",
+            VulnPkgError::State(_) => "state",
+            VulnPkgError::StateVersion { .. } => "state_version",
+            VulnPkgError::HostsFile { .. } | VulnPkgError::HostsBlock { .. } => "hosts_file",
+            VulnPkgError::Credentials { .. } => "credentials_invalid",
+            VulnPkgError::LabParse(_) => "lab_invalid",
+            VulnPkgError::DnsServer(_) => "dns_server",
+            VulnPkgError::ApiServer(_) => "api_server",
+            VulnPkgError::BulkFailed { .. } => "partial_failure",
+            VulnPkgError::DoctorFailed(_) => "doctor_failed",
+        }
+    }
+
+    /// Process exit status for this error
+    pub fn exit_code(&self) -> i32 {
+        let code = self.code();
+        EXIT_CODES
+            .iter()
+            .find(|(_, c, _)| *c == code)
+            .map(|(exit, _, _)| *exit)
+            .unwrap_or(1)
+    }
+}
+
+/// Errors meaning the Docker daemon could not be reached at all
+fn is_connection_error(error: &bollard::errors::Error) -> bool {
+    use bollard::errors::Error;
+    matches!(
+        error,
+        Error::SocketNotFoundError(_)
+            | Error::IOError { .. }
+            | Error::HyperLegacyError { .. }
+            | Error::RequestTimeoutError
+    )
+}
+
+/// Exit code table shown at the end of `--help`
+pub fn exit_codes_help() -> String {
+    let mut help = String::from("Exit codes:\n");
+    for (exit, code, description) in EXIT_CODES {
+        help.push_str(&format!("  {:>3}  {:<20} {}\n", exit, code, description));
+        if *exit == 1 {
+            help.push_str("    2  (usage)              Invalid command-line arguments\n");
+        }
+    }
+    help.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_unique() {
+        for (i, (exit, code, _)) in EXIT_CODES.iter().enumerate() {
+            // 0 is success and 2 is reserved for clap usage errors
+            assert!(
+                *exit != 0 && *exit != 2,
+                "{} uses a reserved exit code",
+                code
+            );
+            for (other_exit, other_code, _) in &EXIT_CODES[i + 1..] {
+                assert_ne!(
+                    exit, other_exit,
+                    "{} and {} share an exit code",
+                    code, other_code
+                );
+                assert_ne!(code, other_code);
+            }
+        }
+    }
+
+    #[test]
+    fn test_exit_code_for_variant() {
+        let error = VulnPkgError::AppNotFound("dvwa".to_string());
+        assert_eq!(error.code(), "app_not_found");
+        assert_eq!(error.exit_code(), 20);
+
+        let error = VulnPkgError::BulkFailed {
+            failed: 1,
+            total: 3,
+        };
+        assert_eq!(error.exit_code(), 70);
+    }
+
+    #[test]
+    fn test_every_code_is_documented() {
+        // code() is an exhaustive match, so every variant has a code; each
+        // code in the table must also be described and listed in the README
+        let readme = include_str!("../README.md");
+        let help = exit_codes_help();
+        for (exit, code, description) in EXIT_CODES {
+            assert!(!description.is_empty(), "{} has no description", code);
+            assert!(help.contains(code), "{} is missing from --help", code);
+            assert!(
+                readme.contains(&format!("`{}`", code)),
+                "{} (exit {}) is missing from the README",
+                code,
+                exit
+            );
+        }
+    }
+}
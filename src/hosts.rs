@@ -0,0 +1,270 @@
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, VulnPkgError};
+use crate::state::State;
+
+const BLOCK_BEGIN: &str = "# BEGIN vuln-pkg (managed by `vuln-pkg hosts`, do not edit)";
+const BLOCK_END: &str = "# END vuln-pkg";
+
+/// Default location of the system hosts file
+pub fn default_hosts_file() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(r"C:\Windows\System32\drivers\etc\hosts")
+    } else {
+        PathBuf::from("/etc/hosts")
+    }
+}
+
+/// All hostnames vuln-pkg should resolve: the Traefik dashboard plus every
+/// app hostname recorded in state, sorted and de-duplicated
+pub fn managed_hostnames(state: &State, domain: &str) -> Vec<String> {
+    let mut hostnames: Vec<String> = state
+        .apps
+        .values()
+        .flat_map(|app| app.hostnames.iter().cloned())
+        .collect();
+    hostnames.push(format!("traefik.{}", domain));
+    hostnames.sort();
+    hostnames.dedup();
+    hostnames
+}
+
+/// Render the delimited vuln-pkg block
+pub fn render_block(address: Ipv4Addr, hostnames: &[String]) -> String {
+    let mut block = String::new();
+    block.push_str(BLOCK_BEGIN);
+    block.push('\n');
+    for hostname in hostnames {
+        block.push_str(&format!("{} {}\n", address, hostname));
+    }
+    block.push_str(BLOCK_END);
+    block.push('\n');
+    block
+}
+
+/// Replace the vuln-pkg block in `contents` with `block`, or remove it when
+/// `block` is None. A new block is appended at the end of the file. Returns
+/// None when the file already has the requested content. Lines outside the
+/// block are kept byte for byte, and the block follows the file's line endings.
+/// A begin marker without an end marker is an error: everything after it
+/// would otherwise be dropped.
+pub fn replace_block(
+    contents: &str,
+    block: Option<&str>,
+) -> std::result::Result<Option<String>, String> {
+    let newline = if contents.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let block = block.map(|b| b.replace('\n', newline));
+    let mut result = String::new();
+    let mut existing = String::new();
+    let mut found = false;
+    let mut in_block = false;
+
+    for line in contents.split_inclusive('\n') {
+        if line.trim() == BLOCK_BEGIN {
+            in_block = true;
+            if !found && let Some(block) = &block {
+                result.push_str(block);
+            }
+            found = true;
+        }
+        if in_block {
+            existing.push_str(line);
+            if line.trim() == BLOCK_END {
+                in_block = false;
+            }
+            continue;
+        }
+        result.push_str(line);
+    }
+
+    if in_block {
+        return Err(format!(
+            "it has a '{}' line without a matching '{}' line; fix or remove the block by hand",
+            BLOCK_BEGIN, BLOCK_END
+        ));
+    }
+
+    match &block {
+        None if !found => return Ok(None),
+        Some(block) if existing == *block => return Ok(None),
+        Some(block) if !found => {
+            if !result.is_empty() {
+                if !result.ends_with('\n') {
+                    result.push_str(newline);
+                }
+                if !result.ends_with(&format!("{0}{0}", newline)) {
+                    result.push_str(newline);
+                }
+            }
+            result.push_str(block);
+        }
+        _ => {}
+    }
+
+    Ok(Some(result))
+}
+
+/// Read the hosts file (a missing file is treated as empty)
+pub fn read_hosts_file(path: &Path) -> Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(hosts_error(path, e)),
+    }
+}
+
+/// Write the hosts file, explaining how to proceed if we lack permission.
+/// The new contents go to a temporary file next to it that then replaces
+/// it, so an interrupted write never leaves a truncated hosts file.
+pub fn write_hosts_file(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.vuln-pkg.tmp", file_name));
+
+    let written = std::fs::write(&temp, contents)
+        .and_then(|()| match std::fs::metadata(path) {
+            // Keep the original mode (hosts must stay world-readable)
+            Ok(metadata) => std::fs::set_permissions(&temp, metadata.permissions()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        })
+        .and_then(|()| std::fs::rename(&temp, path));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(hosts_error(path, e));
+    }
+    Ok(())
+}
+
+fn hosts_error(path: &Path, source: std::io::Error) -> VulnPkgError {
+    VulnPkgError::HostsFile {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+/// Error for a hosts file whose vuln-pkg block can't be safely rewritten
+pub fn block_error(path: &Path, message: String) -> VulnPkgError {
+    VulnPkgError::HostsBlock {
+        path: path.display().to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block() -> String {
+        render_block(
+            Ipv4Addr::LOCALHOST,
+            &[
+                "dvwa.lab.local".to_string(),
+                "traefik.lab.local".to_string(),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_append_block() {
+        let hosts = "127.0.0.1 localhost\n";
+        let updated = replace_block(hosts, Some(&block())).unwrap();
+        assert_eq!(updated, Some(format!("127.0.0.1 localhost\n\n{}", block())));
+
+        // A missing final newline is completed before the block
+        let updated = replace_block("127.0.0.1 localhost", Some(&block())).unwrap();
+        assert_eq!(updated, Some(format!("127.0.0.1 localhost\n\n{}", block())));
+    }
+
+    #[test]
+    fn test_unchanged_block() {
+        let hosts = format!("127.0.0.1 localhost\n\n{}", block());
+        assert_eq!(replace_block(&hosts, Some(&block())).unwrap(), None);
+        assert_eq!(replace_block("127.0.0.1 localhost", None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let hosts = "127.0.0.1 localhost\r\n::1 localhost\r\n";
+        assert_eq!(replace_block(hosts, None).unwrap(), None);
+
+        let updated = replace_block(hosts, Some(&block())).unwrap().unwrap();
+        assert_eq!(
+            updated,
+            format!(
+                "127.0.0.1 localhost\r\n::1 localhost\r\n\r\n{}",
+                block().replace('\n', "\r\n")
+            )
+        );
+        assert_eq!(replace_block(&updated, Some(&block())).unwrap(), None);
+        assert_eq!(
+            replace_block(&updated, None).unwrap().as_deref(),
+            Some("127.0.0.1 localhost\r\n::1 localhost\r\n\r\n")
+        );
+    }
+
+    #[test]
+    fn test_replace_existing_block_in_place() {
+        let hosts = format!(
+            "127.0.0.1 localhost\n{}\n::1 localhost\n",
+            render_block(Ipv4Addr::LOCALHOST, &["old.lab.local".to_string()])
+        );
+        let updated = replace_block(&hosts, Some(&block())).unwrap().unwrap();
+        assert_eq!(
+            updated,
+            format!("127.0.0.1 localhost\n{}\n::1 localhost\n", block())
+        );
+        assert!(!updated.contains("old.lab.local"));
+    }
+
+    #[test]
+    fn test_remove_block() {
+        let hosts = format!("127.0.0.1 localhost\n\n{}", block());
+        assert_eq!(
+            replace_block(&hosts, None).unwrap().as_deref(),
+            Some("127.0.0.1 localhost\n\n")
+        );
+        assert_eq!(replace_block("127.0.0.1 localhost\n", None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_unterminated_block_is_an_error() {
+        let hosts = format!("127.0.0.1 localhost\n{}\n10.0.0.1 intranet\n", BLOCK_BEGIN);
+        assert!(replace_block(&hosts, None).is_err());
+        assert!(replace_block(&hosts, Some(&block())).is_err());
+    }
+
+    #[test]
+    fn test_write_hosts_file_replaces_contents() {
+        let dir = std::env::temp_dir().join(format!("vuln-pkg-hosts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hosts");
+        std::fs::write(&path, "127.0.0.1 localhost\n").unwrap();
+
+        write_hosts_file(&path, &block()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), block());
+        // Only the hosts file is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_managed_hostnames_include_dashboard() {
+        let mut state = State::new();
+        state.apps.insert(
+            "dvwa".to_string(),
+            crate::state::AppState {
+                hostnames: vec!["dvwa.lab.local".to_string()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            managed_hostnames(&state, "lab.local"),
+            vec!["dvwa.lab.local", "traefik.lab.local"]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, VulnPkgError};
+use crate::manifest::{App, Manifest, PortEntry};
+
+/// A lab groups several apps that are started and stopped together,
+/// e.g. a workshop environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lab {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Manifest to resolve apps from (defaults to --manifest-url)
+    #[serde(default)]
+    pub manifest_url: Option<String>,
+    pub apps: Vec<LabApp>,
+}
+
+/// An app in a lab: either just its name or a name with overrides
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LabApp {
+    Name(String),
+    Config(LabAppConfig),
+}
+
+/// Per-lab overrides applied on top of the manifest definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabAppConfig {
+    pub name: String,
+    /// Extra environment variables (`KEY=value`); replaces manifest values with the same key
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// Replaces the manifest port list
+    #[serde(default)]
+    pub ports: Option<Vec<PortEntry>>,
+}
+
+impl LabApp {
+    pub fn name(&self) -> &str {
+        match self {
+            LabApp::Name(name) => name,
+            LabApp::Config(config) => &config.name,
+        }
+    }
+
+    /// Return the manifest app with this lab's overrides applied
+    pub fn apply(&self, app: &App) -> App {
+        let mut app = app.clone();
+        let LabApp::Config(config) = self else {
+            return app;
+        };
+
+        for var in &config.env {
+            let key = env_key(var);
+            app.env.retain(|existing| env_key(existing) != key);
+            app.env.push(var.clone());
+        }
+
+        if let Some(ref ports) = config.ports {
+            app.ports = ports.clone();
+        }
+
+        app
+    }
+}
+
+fn env_key(var: &str) -> &str {
+    var.split_once('=').map(|(key, _)| key).unwrap_or(var)
+}
+
+impl Lab {
+    pub fn parse(yaml: &str) -> Result<Self> {
+        let lab: Lab =
+            serde_yaml::from_str(yaml).map_err(|e| VulnPkgError::LabParse(e.to_string()))?;
+
+        if lab.apps.is_empty() {
+            return Err(VulnPkgError::LabParse(format!(
+                "Lab '{}' does not list any apps",
+                lab.name
+            )));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for app in &lab.apps {
+            if !seen.insert(app.name()) {
+                return Err(VulnPkgError::LabParse(format!(
+                    "Lab '{}' lists '{}' more than once",
+                    lab.name,
+                    app.name()
+                )));
+            }
+        }
+
+        Ok(lab)
+    }
+
+    pub fn from_file(path: &std::path::Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// Resolve every lab app against the manifest, failing before anything
+    /// is started if one of them is missing or invalid with its overrides
+    pub fn resolve(&self, manifest: &Manifest) -> Result<Vec<App>> {
+        self.apps
+            .iter()
+            .map(|lab_app| {
+                let app = manifest
+                    .find_app(lab_app.name())
+                    .map(|app| lab_app.apply(app))
+                    .ok_or_else(|| VulnPkgError::AppNotFound(lab_app.name().to_string()))?;
+                app.validate()?;
+                Ok(app)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Protocol;
+
+    const MANIFEST: &str = r#"
+apps:
+  - name: dvwa
+    version: "1.0"
+    image: vulnerables/web-dvwa
+    ports: [80]
+    env:
+      - MYSQL_ROOT_PASSWORD=root
+      - PHP_DEBUG=0
+  - name: juice-shop
+    version: "1.0"
+    image: bkimminich/juice-shop
+    ports: [3000]
+"#;
+
+    #[test]
+    fn test_parse_lab_with_overrides() {
+        let lab = Lab::parse(
+            r#"
+name: web101
+description: Intro to web security
+apps:
+  - juice-shop
+  - name: dvwa
+    env:
+      - PHP_DEBUG=1
+    ports:
+      - 80
+      - port: 3306
+        protocol: tcp
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(lab.name, "web101");
+        assert_eq!(lab.apps.len(), 2);
+        assert_eq!(lab.apps[0].name(), "juice-shop");
+
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        let apps = lab.resolve(&manifest).unwrap();
+        assert_eq!(apps[0].name, "juice-shop");
+
+        let dvwa = &apps[1];
+        assert_eq!(dvwa.env, vec!["MYSQL_ROOT_PASSWORD=root", "PHP_DEBUG=1"]);
+        assert_eq!(dvwa.direct_ports().len(), 1);
+        assert_eq!(dvwa.direct_ports()[0].protocol, Protocol::Tcp);
+    }
+
+    #[test]
+    fn test_resolve_missing_app() {
+        let lab = Lab::parse("name: broken\napps: [dvwa, nope]\n").unwrap();
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        let err = lab.resolve(&manifest).unwrap_err();
+        assert!(matches!(err, VulnPkgError::AppNotFound(name) if name == "nope"));
+    }
+
+    #[test]
+    fn test_reject_empty_and_duplicate_apps() {
+        assert!(Lab::parse("name: empty\napps: []\n").is_err());
+        let err = Lab::parse("name: dup\napps: [dvwa, dvwa]\n").unwrap_err();
+        assert!(err.to_string().contains("more than once"));
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+
+use crate::docker::DockerManager;
+use crate::error::Result;
+use crate::output::Output;
+use crate::state::StateManager;
+
+/// Owns the shared infrastructure every app depends on (the `vuln-pkg`
+/// network and the Traefik proxy): started on demand, torn down when unused
+pub struct Infrastructure<'a> {
+    docker: &'a DockerManager,
+    state_mgr: &'a StateManager,
+    output: &'a Output,
+}
+
+/// Current state of the shared infrastructure, as shown by `proxy status`
+#[derive(Debug, Serialize)]
+pub struct ProxyStatus {
+    pub running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+    pub dashboard: String,
+    pub network: bool,
+    pub running_apps: usize,
+}
+
+impl<'a> Infrastructure<'a> {
+    pub fn new(docker: &'a DockerManager, state_mgr: &'a StateManager, output: &'a Output) -> Self {
+        Self {
+            docker,
+            state_mgr,
+            output,
+        }
+    }
+
+    /// Make sure the vuln-pkg network exists and, if `needs_proxy` is set,
+    /// that Traefik is running. Returns the network ID.
+    pub async fn ensure(&self, domain: &str, https: bool, needs_proxy: bool) -> Result<String> {
+        self.output.info("Ensuring vuln-pkg network exists");
+        let network_id = self.docker.ensure_network().await?;
+
+        let mut traefik_id = None;
+        if needs_proxy && self.docker.is_traefik_running().await?.is_none() {
+            self.output.info("Starting Traefik reverse proxy");
+            traefik_id = Some(
+                self.docker
+                    .start_traefik(&network_id, domain, https, self.output)
+                    .await?,
+            );
+            self.output.success(&format!(
+                "Traefik running (dashboard: http://traefik.{})",
+                domain
+            ));
+        }
+
+        let mut state = self.state_mgr.load_state()?;
+        state.network_id = Some(network_id.clone());
+        if traefik_id.is_some() {
+            state.traefik_container_id = traefik_id;
+        }
+        self.state_mgr.save_state(&state)?;
+
+        Ok(network_id)
+    }
+
+    /// Stop and remove Traefik. Returns false if it was not running.
+    pub async fn stop_proxy(&self) -> Result<bool> {
+        let stopped = self.docker.find_traefik_container().await?.is_some();
+        if stopped {
+            self.docker.stop_traefik().await?;
+        }
+
+        let mut state = self.state_mgr.load_state()?;
+        if state.traefik_container_id.take().is_some() {
+            self.state_mgr.save_state(&state)?;
+        }
+
+        Ok(stopped)
+    }
+
+    /// Tear down what no app needs any more: Traefik once no app is running,
+    /// and the network once no app container is left at all
+    pub async fn release(&self) -> Result<()> {
+        let containers = self.docker.list_vuln_pkg_containers().await?;
+        if containers.iter().any(|(_, _, running)| *running) {
+            return Ok(());
+        }
+
+        if self.docker.find_traefik_container().await?.is_some() {
+            self.output.info("No more apps running, stopping Traefik");
+            self.stop_proxy().await?;
+        }
+
+        // Stopped containers stay attached to the network, so it has to
+        // outlive them or they could not be started again
+        if containers.is_empty()
+            && let Some(network_id) = self.docker.find_network().await?
+        {
+            self.output
+                .info("No more apps installed, removing vuln-pkg network");
+            if let Err(e) = self.docker.remove_network(&network_id).await {
+                // Something outside vuln-pkg may still be attached
+                self.output
+                    .warning(&format!("Could not remove vuln-pkg network: {}", e));
+                return Ok(());
+            }
+
+            let mut state = self.state_mgr.load_state()?;
+            state.network_id = None;
+            self.state_mgr.save_state(&state)?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn status(&self, domain: &str) -> Result<ProxyStatus> {
+        let traefik = self.docker.find_traefik_container().await?;
+        let running_apps = self.docker.count_running_apps().await?;
+
+        Ok(ProxyStatus {
+            running: matches!(traefik, Some((_, true))),
+            container_id: traefik.map(|(id, _)| id),
+            dashboard: format!("http://traefik.{}", domain),
+            network: self.docker.network_exists().await?,
+            running_apps,
+        })
+    }
+}
@@ -1,20 +1,42 @@
 mod cli;
+mod context;
+mod credentials;
+mod dev;
+mod dns;
 mod docker;
+mod doctor;
 mod error;
+mod hosts;
+mod lab;
+mod lifecycle;
 mod manifest;
+mod outdated;
 mod output;
+mod query;
+mod reconcile;
+mod select;
+mod serve;
 mod state;
+mod ttl;
 
+use std::collections::HashMap;
 use std::net::Ipv4Addr;
 
 use clap::Parser;
+use futures::StreamExt;
 
-use cli::{Cli, Commands, ManifestCommands};
-use docker::DockerManager;
+use cli::{CacheCommands, Cli, Commands, HostsCommands, ManifestCommands, ProxyCommands};
+use context::BuildContext;
+use credentials::GitCredentials;
+use docker::{BuildOptions, DockerManager};
 use error::{Result, VulnPkgError};
-use manifest::{Manifest, PackageType};
-use output::{Output, StatusInfo};
-use state::{AllocatedPort, ImageSource, StateManager};
+use lab::Lab;
+use lifecycle::Infrastructure;
+use manifest::{Manifest, PackageType, Protocol};
+use output::{AppEndpoints, BulkOutcome, Output, StatusInfo};
+use query::{Query, SortKey};
+use select::Selector;
+use state::{AllocatedPort, AppState, Endpoint, ImageSource, StateManager};
 
 /// Generate a sslip.io domain from an IP address for zero-config DNS resolution
 /// e.g., 127.0.0.1 -> "127.0.0.1.sslip.io"
@@ -36,8 +58,8 @@ async fn main() {
     let output = Output::new(cli.json);
 
     if let Err(e) = run(cli, &output).await {
-        output.error(&e.to_string());
-        std::process::exit(1);
+        output.fatal(&e);
+        std::process::exit(e.exit_code());
     }
 }
 
@@ -46,8 +68,11 @@ async fn run(cli: Cli, output: &Output) -> Result<()> {
     let state_mgr = StateManager::new()?;
     state_mgr.init()?;
 
-    // Sync state with Docker reality (containers may have stopped/been removed)
-    sync_state_with_docker(&state_mgr).await?;
+    // Sync state with Docker reality (containers may have stopped/been removed).
+    // doctor diagnoses an unreachable Docker itself, so it must not fail here.
+    if !matches!(cli.command, Commands::Doctor { .. }) {
+        sync_state_with_docker(&state_mgr).await?;
+    }
 
     // Resolve domain: use provided domain or generate sslip.io domain for zero-config
     let domain = cli
@@ -55,37 +80,262 @@ async fn run(cli: Cli, output: &Output) -> Result<()> {
         .unwrap_or_else(|| sslip_domain(cli.resolve_address));
 
     let auto_accept = cli.yes;
-
-    match cli.command {
-        Commands::List => cmd_list(&cli.manifest_url, &state_mgr, output, auto_accept).await,
-        Commands::Search { query } => {
-            cmd_search(&query, &cli.manifest_url, &state_mgr, output, auto_accept).await
+    let resolve_address = cli.resolve_address;
+    let sync_hosts =
+        cli.sync_hosts && matches!(cli.command, Commands::Run { .. } | Commands::Remove { .. });
+
+    let result = match cli.command {
+        Commands::List { filter, sort } => {
+            let filter = filter.unwrap_or_default();
+            cmd_list(
+                &filter,
+                sort,
+                &cli.manifest_url,
+                &state_mgr,
+                output,
+                auto_accept,
+            )
+            .await
+        }
+        Commands::Search { query, sort } => {
+            cmd_search(
+                &query,
+                sort,
+                &cli.manifest_url,
+                &state_mgr,
+                output,
+                auto_accept,
+            )
+            .await
+        }
+        Commands::Info { app } => {
+            cmd_info(
+                &app,
+                &cli.manifest_url,
+                &state_mgr,
+                output,
+                resolve_address,
+                auto_accept,
+            )
+            .await
+        }
+        Commands::Open { app, port, print } => {
+            let target = OpenTarget {
+                port: port.as_deref(),
+                print,
+                resolve_address,
+            };
+            cmd_open(
+                &app,
+                &cli.manifest_url,
+                &state_mgr,
+                output,
+                &target,
+                auto_accept,
+            )
+            .await
         }
         Commands::Install { app } => {
             cmd_install(&app, &cli.manifest_url, &state_mgr, output, auto_accept).await
         }
-        Commands::Run { app } => {
-            cmd_run(
+        Commands::Run { apps, bulk, ttl } => {
+            let opts = StartOptions {
+                domain: &domain,
+                https: cli.https,
+                ttl,
+            };
+            let selector = Selector {
+                patterns: &apps,
+                tags: &bulk.tag,
+                all: bulk.all,
+            };
+            if selector.is_bulk() {
+                let manifest =
+                    fetch_manifest(&cli.manifest_url, &state_mgr, output, auto_accept).await?;
+                cmd_run_many(&manifest, &selector, bulk.jobs, &state_mgr, output, &opts).await
+            } else {
+                cmd_run(
+                    &apps[0],
+                    &cli.manifest_url,
+                    &state_mgr,
+                    output,
+                    &opts,
+                    auto_accept,
+                )
+                .await
+            }
+        }
+        Commands::Stop { apps, bulk } => {
+            let selector = Selector {
+                patterns: &apps,
+                tags: &bulk.tag,
+                all: bulk.all,
+            };
+            if selector.is_bulk() {
+                let tags = manifest_tags(
+                    &selector,
+                    &cli.manifest_url,
+                    &state_mgr,
+                    output,
+                    auto_accept,
+                )
+                .await?;
+                cmd_stop_many(&selector, &tags, bulk.jobs, &state_mgr, output).await
+            } else {
+                cmd_stop(&apps[0], &state_mgr, output).await
+            }
+        }
+        Commands::Remove { apps, purge, bulk } => {
+            let selector = Selector {
+                patterns: &apps,
+                tags: &bulk.tag,
+                all: bulk.all,
+            };
+            if selector.is_bulk() {
+                let tags = manifest_tags(
+                    &selector,
+                    &cli.manifest_url,
+                    &state_mgr,
+                    output,
+                    auto_accept,
+                )
+                .await?;
+                cmd_remove_many(&selector, &tags, bulk.jobs, &state_mgr, output, purge).await
+            } else {
+                cmd_remove(&apps[0], &state_mgr, output, purge).await
+            }
+        }
+        Commands::Rebuild {
+            app,
+            no_cache,
+            build_args,
+        } => {
+            cmd_rebuild(
                 &app,
+                no_cache,
+                &build_args,
                 &cli.manifest_url,
                 &state_mgr,
                 output,
-                &domain,
-                cli.https,
                 auto_accept,
             )
             .await
         }
-        Commands::Stop { app } => cmd_stop(&app, &state_mgr, output).await,
-        Commands::Remove { app, purge } => cmd_remove(&app, &state_mgr, output, purge).await,
-        Commands::Rebuild { app } => {
-            cmd_rebuild(&app, &cli.manifest_url, &state_mgr, output, auto_accept).await
+        Commands::Dev {
+            path,
+            name,
+            ports,
+            dockerfile,
+            build_args,
+            keep,
+        } => {
+            let options = dev::DevOptions {
+                name,
+                ports,
+                dockerfile,
+                build_args,
+            };
+            let opts = StartOptions {
+                domain: &domain,
+                https: cli.https,
+                ttl: None,
+            };
+            cmd_dev(&path, options, keep, &state_mgr, output, &opts).await
+        }
+        Commands::Outdated => {
+            cmd_outdated(&cli.manifest_url, &state_mgr, output, auto_accept).await
         }
         Commands::Status => cmd_status(&state_mgr, output).await,
+        Commands::Up { lab, ttl } => {
+            let opts = StartOptions {
+                domain: &domain,
+                https: cli.https,
+                ttl,
+            };
+            cmd_up(
+                &lab,
+                &cli.manifest_url,
+                &state_mgr,
+                output,
+                &opts,
+                auto_accept,
+            )
+            .await
+        }
+        Commands::Gc {
+            dry_run,
+            daemon,
+            interval,
+        } => cmd_gc(&state_mgr, output, dry_run, daemon.then_some(interval)).await,
+        Commands::Down { lab, purge } => cmd_down(&lab, &state_mgr, output, purge).await,
+        Commands::Hosts { command } => {
+            cmd_hosts(command, &state_mgr, output, &domain, resolve_address)
+        }
+        Commands::Dns {
+            listen,
+            upstream,
+            no_forward,
+        } => {
+            let upstream = if no_forward { None } else { Some(upstream) };
+            dns::DnsResponder::new(&domain, resolve_address, upstream)
+                .serve(listen, output)
+                .await
+        }
+        Commands::Serve {
+            listen,
+            socket,
+            token,
+        } => {
+            // Requests cannot answer the acceptance prompt, so settle it now
+            fetch_manifest(&cli.manifest_url, &state_mgr, output, auto_accept).await?;
+
+            let listen = match socket {
+                Some(path) => serve::Listen::Unix(path),
+                None => serve::Listen::Tcp {
+                    addr: listen,
+                    token: match token.or_else(|| std::env::var("VULN_PKG_API_TOKEN").ok()) {
+                        Some(token) => token,
+                        None => serve::generate_token()?,
+                    },
+                },
+            };
+            let globals = serve::Globals {
+                yes: auto_accept,
+                manifest_url: cli.manifest_url.clone(),
+                resolve_address,
+                domain: Some(domain.clone()),
+                https: cli.https,
+            };
+            serve::serve(listen, globals, run_request, output).await
+        }
+        Commands::Doctor { fix } => {
+            let ctx = doctor::DoctorContext {
+                domain: &domain,
+                resolve_address,
+                https: cli.https,
+            };
+            cmd_doctor(&state_mgr, output, &ctx, fix).await
+        }
+        Commands::Proxy { command } => {
+            cmd_proxy(command, &state_mgr, output, &domain, cli.https).await
+        }
+        Commands::Cache { command } => cmd_cache(command, &state_mgr, output),
+        Commands::Adopt { dry_run } => cmd_adopt(&state_mgr, output, dry_run).await,
         Commands::Manifest { command } => {
             cmd_manifest(command, &cli.manifest_url, &state_mgr, output, auto_accept).await
         }
+    };
+
+    if result.is_ok() && sync_hosts {
+        hosts_sync(&state_mgr, output, &domain, resolve_address, None, false)?;
     }
+
+    result
+}
+
+/// Entry point for API requests; boxed so `serve` does not depend on the type of `run`'s future
+fn run_request(cli: Cli, output: Output) -> serve::HandlerFuture {
+    Box::pin(async move { run(cli, &output).await })
 }
 
 /// Sync state with Docker reality - update running status based on actual container state
@@ -128,7 +378,9 @@ async fn sync_state_with_docker(state_mgr: &StateManager) -> Result<()> {
         }
     }
 
-    if changed {
+    // A state file from a newer vuln-pkg can't be written; skipping the
+    // sync keeps read-only commands working, and writes fail on their own
+    if changed && state.version <= state::STATE_VERSION {
         state_mgr.save_state(&state)?;
     }
 
@@ -156,6 +408,13 @@ async fn fetch_manifest(
         let accepted = if auto_accept {
             output.info("Auto-accepting manifest (-y flag)");
             true
+        } else if output.is_captured() {
+            // Nobody to ask: API clients can only use manifests accepted beforehand
+            false
+        } else if output.is_json() {
+            // A prompt would corrupt the NDJSON stream on stdout
+            output.warning("--json cannot prompt for acceptance; pass -y to accept this manifest");
+            false
         } else {
             // Interactive prompt with show option
             loop {
@@ -210,6 +469,8 @@ async fn fetch_manifest(
 }
 
 async fn cmd_list(
+    filter: &Query,
+    sort: SortKey,
     manifest_url: &str,
     state_mgr: &StateManager,
     output: &Output,
@@ -218,50 +479,152 @@ async fn cmd_list(
     let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
     let state = state_mgr.load_state()?;
 
-    output.list_apps(&manifest.apps, &state.apps);
+    let apps = filter.apply(&manifest.apps, &state.apps, sort);
+    output.list_apps(&apps, &state.apps);
     Ok(())
 }
 
 async fn cmd_search(
-    query: &str,
+    query: &Query,
+    sort: SortKey,
+    manifest_url: &str,
+    state_mgr: &StateManager,
+    output: &Output,
+    auto_accept: bool,
+) -> Result<()> {
+    let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
+    let state = state_mgr.load_state()?;
+
+    let matching_apps = query.apply(&manifest.apps, &state.apps, sort);
+    output.search_results(query.as_str(), &matching_apps, &state.apps);
+    Ok(())
+}
+
+async fn cmd_info(
+    app_name: &str,
     manifest_url: &str,
     state_mgr: &StateManager,
     output: &Output,
+    resolve_address: Ipv4Addr,
     auto_accept: bool,
 ) -> Result<()> {
     let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
+    let app = manifest
+        .find_app(app_name)
+        .ok_or_else(|| VulnPkgError::AppNotFound(app_name.to_string()))?;
+
     let state = state_mgr.load_state()?;
+    let app_state = state.apps.get(app_name);
+
+    // Docker being down shouldn't hide the rest of the details
+    let image_size = match DockerManager::new() {
+        Ok(docker) => docker
+            .image_size(&app.effective_image())
+            .await
+            .unwrap_or(None),
+        Err(_) => None,
+    };
+
+    let endpoints = app_state
+        .filter(|s| s.running)
+        .map(|s| s.endpoints(app, &resolve_address.to_string()))
+        .unwrap_or_default();
+
+    output.app_info(app, app_state, image_size, &endpoints);
+    Ok(())
+}
 
-    let query_lower = query.to_lowercase();
+/// What `open` should open and how
+struct OpenTarget<'a> {
+    /// Port label or container port; defaults to the first HTTP port
+    port: Option<&'a str>,
+    /// Only print the URL instead of launching a browser
+    print: bool,
+    resolve_address: Ipv4Addr,
+}
 
-    let matching_apps: Vec<&manifest::App> = manifest
+async fn cmd_open(
+    app_name: &str,
+    manifest_url: &str,
+    state_mgr: &StateManager,
+    output: &Output,
+    target: &OpenTarget<'_>,
+    auto_accept: bool,
+) -> Result<()> {
+    let state = state_mgr.load_state()?;
+    let app_state = state
         .apps
-        .iter()
-        .filter(|app| {
-            // Match against name
-            if app.name.to_lowercase().contains(&query_lower) {
-                return true;
-            }
-            // Match against description
-            if app.description.to_lowercase().contains(&query_lower) {
-                return true;
-            }
-            // Match against tags
-            if app
-                .tags
-                .iter()
-                .any(|tag| tag.to_lowercase().contains(&query_lower))
-            {
-                return true;
-            }
-            false
-        })
-        .collect();
+        .get(app_name)
+        .ok_or_else(|| VulnPkgError::AppNotInstalled(app_name.to_string()))?;
+    if !app_state.running {
+        return Err(VulnPkgError::AppNotRunning(app_name.to_string()));
+    }
+
+    let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
+    let app = manifest
+        .find_app(app_name)
+        .ok_or_else(|| VulnPkgError::AppNotFound(app_name.to_string()))?;
+
+    let endpoints = app_state.endpoints(app, &target.resolve_address.to_string());
+    let endpoint = match target.port {
+        Some(port) => {
+            AppState::find_endpoint(&endpoints, port).ok_or_else(|| VulnPkgError::PortNotFound {
+                app: app_name.to_string(),
+                port: port.to_string(),
+            })?
+        }
+        None => endpoints
+            .iter()
+            .find(|e| e.protocol == Protocol::Http)
+            .or(endpoints.first())
+            .ok_or_else(|| VulnPkgError::PortNotFound {
+                app: app_name.to_string(),
+                port: "any".to_string(),
+            })?,
+    };
+
+    if endpoint.protocol != Protocol::Http {
+        output.connection_info(endpoint, &connection_command(endpoint));
+        return Ok(());
+    }
 
-    output.search_results(query, &matching_apps, &state.apps);
+    let launched = !target.print && !output.is_json() && launch_browser(&endpoint.url);
+    output.app_opened(endpoint, launched);
     Ok(())
 }
 
+/// Ready-to-paste command for a TCP/UDP endpoint (`host:port`)
+fn connection_command(endpoint: &Endpoint) -> String {
+    let (host, port) = endpoint
+        .url
+        .rsplit_once(':')
+        .unwrap_or((endpoint.url.as_str(), ""));
+    match endpoint.protocol {
+        Protocol::Udp => format!("nc -u {} {}", host, port),
+        _ => format!("nc {} {}", host, port),
+    }
+}
+
+/// Open `url` with the system browser; returns false if none could be started
+fn launch_browser(url: &str) -> bool {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+
+    command
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
 async fn cmd_install(
     app_name: &str,
     manifest_url: &str,
@@ -275,6 +638,48 @@ async fn cmd_install(
         .find_app(app_name)
         .ok_or_else(|| VulnPkgError::AppNotFound(app_name.to_string()))?;
 
+    install_app(app, state_mgr, output).await?;
+
+    output.app_installed(app);
+    Ok(())
+}
+
+/// Build the image of a dockerfile or git package, returning the built
+/// commit for git packages
+async fn build_app_image(
+    docker: &DockerManager,
+    app: &manifest::App,
+    build: &BuildOptions,
+    state_mgr: &StateManager,
+    output: &Output,
+) -> Result<Option<String>> {
+    let effective_image = app.effective_image();
+    match app.package_type {
+        PackageType::Prebuilt => Err(VulnPkgError::AppNotRebuildable(app.name.clone())),
+        PackageType::Dockerfile => {
+            if let Some(ref dockerfile) = app.dockerfile {
+                // Inline Dockerfile
+                docker
+                    .build_from_dockerfile(dockerfile, &effective_image, build, output)
+                    .await?;
+            } else if app.dockerfile_url.is_some() {
+                // Remote Dockerfile
+                docker
+                    .build_from_dockerfile_url(app, build, state_mgr, output)
+                    .await?;
+            }
+            Ok(None)
+        }
+        PackageType::Git => docker.build_from_git(app, build, state_mgr, output).await,
+        PackageType::Local => {
+            docker.build_from_local(app, build, output).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Pull or build the image for an app and record it in state
+async fn install_app(app: &manifest::App, state_mgr: &StateManager, output: &Output) -> Result<()> {
     let docker = DockerManager::new()?;
     let effective_image = app.effective_image();
 
@@ -289,46 +694,15 @@ async fn cmd_install(
             }
             (ImageSource::Prebuilt, None)
         }
-        PackageType::Dockerfile => {
-            // Build from Dockerfile
-            if let Some(ref dockerfile) = app.dockerfile {
-                // Inline Dockerfile
-                docker
-                    .build_from_dockerfile(dockerfile, &effective_image, output)
-                    .await?;
-            } else if let Some(ref url) = app.dockerfile_url {
-                // Remote Dockerfile
-                docker
-                    .build_from_dockerfile_url(
-                        url,
-                        app.context_url.as_deref(),
-                        &effective_image,
-                        output,
-                    )
-                    .await?;
-            }
-            (ImageSource::Dockerfile, None)
-        }
-        PackageType::Git => {
-            // Build from git repository
-            let repo = app.repo.as_ref().ok_or_else(|| {
-                VulnPkgError::ManifestValidation(format!(
-                    "Git app '{}' missing repo field",
-                    app_name
-                ))
-            })?;
-
-            let commit = docker
-                .build_from_git(
-                    repo,
-                    app.git_ref.as_deref(),
-                    app.dockerfile_path.as_deref(),
-                    &effective_image,
-                    state_mgr,
-                    output,
-                )
-                .await?;
-            (ImageSource::Git, commit)
+        PackageType::Dockerfile | PackageType::Git | PackageType::Local => {
+            let source = match app.package_type {
+                PackageType::Git => ImageSource::Git,
+                PackageType::Local => ImageSource::Local,
+                _ => ImageSource::Dockerfile,
+            };
+            let build = BuildOptions::for_app(app);
+            let commit = build_app_image(&docker, app, &build, state_mgr, output).await?;
+            (source, commit)
         }
     };
 
@@ -342,7 +716,6 @@ async fn cmd_install(
     app_state.built_at = Some(chrono::Utc::now().to_rfc3339());
     state_mgr.save_state(&state)?;
 
-    output.app_installed(app);
     Ok(())
 }
 
@@ -351,8 +724,7 @@ async fn cmd_run(
     manifest_url: &str,
     state_mgr: &StateManager,
     output: &Output,
-    domain: &str,
-    https: bool,
+    opts: &StartOptions<'_>,
     auto_accept: bool,
 ) -> Result<()> {
     let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
@@ -361,42 +733,80 @@ async fn cmd_run(
         .find_app(app_name)
         .ok_or_else(|| VulnPkgError::AppNotFound(app_name.to_string()))?;
 
-    let mut state = state_mgr.load_state()?;
+    let (hostnames, allocated_ports) = start_app(app, state_mgr, output, opts).await?;
+
+    output.app_running(app, &hostnames, &allocated_ports, opts.domain, opts.https);
+    Ok(())
+}
+
+/// Settings shared by every app started in one invocation
+struct StartOptions<'a> {
+    domain: &'a str,
+    https: bool,
+    /// Lifetime after which `vuln-pkg gc` removes the app; without one, an
+    /// app keeps the expiry it already has
+    ttl: Option<chrono::TimeDelta>,
+}
+
+impl StartOptions<'_> {
+    fn expires_at(&self) -> Result<Option<String>> {
+        let Some(ttl) = self.ttl else {
+            return Ok(None);
+        };
+        chrono::Utc::now()
+            .checked_add_signed(ttl)
+            .map(|at| Some(at.to_rfc3339()))
+            .ok_or_else(|| VulnPkgError::State(format!("TTL of {} is out of range", ttl)))
+    }
+}
+
+/// Start an app's container (creating it, and installing the image, if
+/// needed) and return its HTTP hostnames and allocated TCP/UDP ports
+async fn start_app(
+    app: &manifest::App,
+    state_mgr: &StateManager,
+    output: &Output,
+    opts: &StartOptions<'_>,
+) -> Result<(Vec<String>, Vec<AllocatedPort>)> {
+    let app_name = app.name.as_str();
     let docker = DockerManager::new()?;
+    let has_http_ports = !app.http_ports().is_empty();
+    // Worked out up front so a bad TTL fails before anything is started
+    let expires_at = opts.expires_at()?;
 
     // Check if container already exists
-    if let Some((container_id, is_running)) = docker.find_app_container(app_name).await? {
-        if is_running {
-            return Err(VulnPkgError::AppAlreadyRunning(app_name.to_string()));
+    let existing = match docker.find_app_container(app_name).await? {
+        Some((_, true)) => return Err(VulnPkgError::AppAlreadyRunning(app_name.to_string())),
+        Some((container_id, false)) => {
+            if docker
+                .container_matches(&container_id, app, opts.domain, opts.https)
+                .await?
+            {
+                Some(container_id)
+            } else {
+                // Env, ports (e.g. lab overrides), domain or HTTPS changed,
+                // and a container's config is fixed when it is created
+                output.info(&format!(
+                    "Settings for {} changed; recreating its container",
+                    app.name
+                ));
+                docker.remove_container(&container_id).await?;
+                None
+            }
         }
+        None => None,
+    };
 
+    if let Some(container_id) = existing {
         // Container exists but is stopped - ensure Traefik is running first (if has HTTP ports)
-        output.info("Ensuring vuln-pkg network exists");
-        let network_id = docker.ensure_network().await?;
-        state.network_id = Some(network_id.clone());
-
-        let has_http_ports = !app.http_ports().is_empty();
-        if has_http_ports && docker.is_traefik_running().await?.is_none() {
-            output.info("Starting Traefik reverse proxy");
-            let traefik_id = docker
-                .start_traefik(&network_id, domain, https, output)
-                .await?;
-            state.traefik_container_id = Some(traefik_id);
-            output.success(&format!(
-                "Traefik running (dashboard: http://traefik.{})",
-                domain
-            ));
-        }
+        Infrastructure::new(&docker, state_mgr, output)
+            .ensure(opts.domain, opts.https, has_http_ports)
+            .await?;
 
         // Start existing container
         output.info(&format!("Starting existing container for {}", app.name));
         docker.start_container(&container_id).await?;
 
-        // Update state - restore hostnames from previous state or regenerate
-        let app_state = state.apps.entry(app.name.clone()).or_default();
-        app_state.running = true;
-        app_state.container_id = Some(container_id);
-
         // Regenerate HTTP hostnames
         let http_ports = app.http_ports();
         let hostnames: Vec<String> = http_ports
@@ -404,20 +814,29 @@ async fn cmd_run(
             .enumerate()
             .map(|(i, port_config)| {
                 if i == 0 {
-                    format!("{}.{}", app.name, domain)
+                    format!("{}.{}", app.name, opts.domain)
                 } else {
-                    format!("{}-{}.{}", app.name, port_config.port, domain)
+                    format!("{}-{}.{}", app.name, port_config.port, opts.domain)
                 }
             })
             .collect();
+
+        // Update state (reloaded so concurrent starts don't overwrite each other)
+        let mut state = state_mgr.load_state()?;
+        let app_state = state.apps.entry(app.name.clone()).or_default();
+        app_state.running = true;
+        app_state.container_id = Some(container_id);
         app_state.hostnames = hostnames.clone();
+        // `https` keeps the value recorded when the container was created
+        if let Some(expires_at) = expires_at {
+            app_state.expires_at = Some(expires_at);
+        }
 
         // Get existing allocated ports (they should still be valid)
         let allocated_ports = app_state.allocated_ports.clone();
         state_mgr.save_state(&state)?;
 
-        output.app_running(app, &hostnames, &allocated_ports, domain, https);
-        return Ok(());
+        return Ok((hostnames, allocated_ports));
     }
 
     // Container doesn't exist - need to create it
@@ -426,76 +845,114 @@ async fn cmd_run(
     // Ensure image exists (install if needed)
     if !docker.image_exists(&effective_image).await? {
         // Delegate to install logic for building/pulling
-        cmd_install(app_name, manifest_url, state_mgr, output, auto_accept).await?;
-        // Reload state after install
-        state = state_mgr.load_state()?;
+        install_app(app, state_mgr, output).await?;
     }
 
-    // Ensure network exists
-    output.info("Ensuring vuln-pkg network exists");
-    let network_id = docker.ensure_network().await?;
-    state.network_id = Some(network_id.clone());
-
-    // Ensure Traefik is running (only needed for HTTP ports)
-    let has_http_ports = !app.http_ports().is_empty();
-    if has_http_ports && docker.is_traefik_running().await?.is_none() {
-        output.info("Starting Traefik reverse proxy");
-        let traefik_id = docker
-            .start_traefik(&network_id, domain, https, output)
-            .await?;
-        state.traefik_container_id = Some(traefik_id);
-        output.success(&format!(
-            "Traefik running (dashboard: http://traefik.{})",
-            domain
-        ));
-    }
+    // Ensure network exists and Traefik is running (only needed for HTTP ports)
+    let network_id = Infrastructure::new(&docker, state_mgr, output)
+        .ensure(opts.domain, opts.https, has_http_ports)
+        .await?;
 
     // Allocate ports for TCP/UDP direct mappings
-    let direct_ports = app.direct_ports();
-    let allocated_ports: Vec<AllocatedPort> = if !direct_ports.is_empty() {
-        let host_ports = state.allocate_ports(direct_ports.len()).ok_or_else(|| {
-            VulnPkgError::State("No available ports in allocation range".to_string())
-        })?;
-
-        direct_ports
-            .iter()
-            .zip(host_ports.iter())
-            .map(|(port_config, &host_port)| AllocatedPort {
-                container_port: port_config.port,
-                host_port,
-                protocol: port_config.protocol.clone(),
-                label: port_config.label.clone(),
-            })
-            .collect()
-    } else {
-        Vec::new()
-    };
+    let allocated_ports = reserve_ports(app, state_mgr)?;
 
     // Create and start container
-    output.info(&format!("Creating container for {}", app.name));
-    let (container_id, hostnames) = docker
-        .create_container(app, &network_id, domain, https, &allocated_ports)
-        .await?;
+    let created = async {
+        output.info(&format!("Creating container for {}", app.name));
+        let (container_id, hostnames) = docker
+            .create_container(app, &network_id, opts.domain, opts.https, &allocated_ports)
+            .await?;
 
-    output.info("Starting container");
-    docker.start_container(&container_id).await?;
+        output.info("Starting container");
+        docker.start_container(&container_id).await?;
+        Ok((container_id, hostnames))
+    }
+    .await;
+
+    let (container_id, hostnames) = match created {
+        Ok(created) => created,
+        Err(e) => {
+            // Give the reserved ports back
+            let mut state = state_mgr.load_state()?;
+            if let Some(app_state) = state.apps.get_mut(app_name) {
+                app_state.allocated_ports.clear();
+                state_mgr.save_state(&state)?;
+            }
+            return Err(e);
+        }
+    };
 
     // Update state
+    let mut state = state_mgr.load_state()?;
     let app_state = state.apps.entry(app.name.clone()).or_default();
     app_state.installed = true;
     app_state.running = true;
     app_state.container_id = Some(container_id);
     app_state.hostnames = hostnames.clone();
     app_state.allocated_ports = allocated_ports.clone();
+    app_state.https = opts.https;
+    if let Some(expires_at) = expires_at {
+        app_state.expires_at = Some(expires_at);
+    }
     state_mgr.save_state(&state)?;
 
-    output.app_running(app, &hostnames, &allocated_ports, domain, https);
+    Ok((hostnames, allocated_ports))
+}
 
-    Ok(())
+/// Allocate host ports for an app's TCP/UDP ports and record them in state
+/// immediately, so apps started concurrently never get the same port
+fn reserve_ports(app: &manifest::App, state_mgr: &StateManager) -> Result<Vec<AllocatedPort>> {
+    let direct_ports = app.direct_ports();
+    if direct_ports.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut state = state_mgr.load_state()?;
+    if let Some(app_state) = state.apps.get_mut(&app.name) {
+        app_state.allocated_ports.clear();
+    }
+
+    let host_ports = state
+        .allocate_ports(direct_ports.len())
+        .ok_or_else(|| VulnPkgError::State("No available ports in allocation range".to_string()))?;
+
+    let allocated_ports: Vec<AllocatedPort> = direct_ports
+        .iter()
+        .zip(host_ports.iter())
+        .map(|(port_config, &host_port)| AllocatedPort {
+            container_port: port_config.port,
+            host_port,
+            protocol: port_config.protocol.clone(),
+            label: port_config.label.clone(),
+        })
+        .collect();
+
+    state
+        .apps
+        .entry(app.name.clone())
+        .or_default()
+        .allocated_ports = allocated_ports.clone();
+    state_mgr.save_state(&state)?;
+
+    Ok(allocated_ports)
 }
 
 async fn cmd_stop(app_name: &str, state_mgr: &StateManager, output: &Output) -> Result<()> {
-    let mut state = state_mgr.load_state()?;
+    stop_app(app_name, state_mgr, output).await?;
+
+    // Stop Traefik if this was the last running app
+    let docker = DockerManager::new()?;
+    Infrastructure::new(&docker, state_mgr, output)
+        .release()
+        .await?;
+
+    output.app_stopped(app_name);
+    Ok(())
+}
+
+/// Stop an app's container, keeping it around for a later `run`
+async fn stop_app(app_name: &str, state_mgr: &StateManager, output: &Output) -> Result<()> {
+    let state = state_mgr.load_state()?;
 
     let app_state = state
         .apps
@@ -523,11 +980,12 @@ async fn cmd_stop(app_name: &str, state_mgr: &StateManager, output: &Output) ->
     }
 
     // Update state
-    let app_state = state.apps.get_mut(app_name).unwrap();
-    app_state.running = false;
+    let mut state = state_mgr.load_state()?;
+    if let Some(app_state) = state.apps.get_mut(app_name) {
+        app_state.running = false;
+    }
     state_mgr.save_state(&state)?;
 
-    output.app_stopped(app_name);
     Ok(())
 }
 
@@ -537,7 +995,26 @@ async fn cmd_remove(
     output: &Output,
     purge: bool,
 ) -> Result<()> {
-    let mut state = state_mgr.load_state()?;
+    remove_app(app_name, state_mgr, output, purge).await?;
+
+    // Tear down Traefik and the network if this was the last app
+    let docker = DockerManager::new()?;
+    Infrastructure::new(&docker, state_mgr, output)
+        .release()
+        .await?;
+
+    output.app_removed(app_name);
+    Ok(())
+}
+
+/// Stop and remove an app's container, dropping it from state
+async fn remove_app(
+    app_name: &str,
+    state_mgr: &StateManager,
+    output: &Output,
+    purge: bool,
+) -> Result<()> {
+    let state = state_mgr.load_state()?;
 
     let app_state = state
         .apps
@@ -565,24 +1042,218 @@ async fn cmd_remove(
     }
 
     // Update state
+    let mut state = state_mgr.load_state()?;
     state.apps.remove(app_name);
+    state_mgr.save_state(&state)?;
 
-    // Check if this was the last app - if so, stop Traefik
-    let running_apps = docker.count_running_apps().await?;
-    if running_apps == 0 {
-        output.info("No more apps running, stopping Traefik");
-        docker.stop_traefik().await?;
-        state.traefik_container_id = None;
+    Ok(())
+}
+
+/// Tags of every manifest app, fetched only when the selector filters by tag
+async fn manifest_tags(
+    selector: &Selector<'_>,
+    manifest_url: &str,
+    state_mgr: &StateManager,
+    output: &Output,
+    auto_accept: bool,
+) -> Result<HashMap<String, Vec<String>>> {
+    if selector.tags.is_empty() {
+        return Ok(HashMap::new());
     }
 
-    state_mgr.save_state(&state)?;
+    let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
+    Ok(manifest
+        .apps
+        .into_iter()
+        .map(|app| (app.name, app.tags))
+        .collect())
+}
 
-    output.app_removed(app_name);
+/// Select installed apps; `running_only` restricts `--all` to running apps
+fn select_installed(
+    selector: &Selector<'_>,
+    tags: &HashMap<String, Vec<String>>,
+    state_mgr: &StateManager,
+    running_only: bool,
+) -> Result<select::Selection> {
+    let state = state_mgr.load_state()?;
+    let mut names: Vec<&String> = state
+        .apps
+        .iter()
+        .filter(|(_, app_state)| !(running_only && selector.all) || app_state.running)
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+
+    let no_tags = Vec::new();
+    let candidates: Vec<(&str, &[String])> = names
+        .iter()
+        .map(|name| {
+            let app_tags = tags.get(name.as_str()).unwrap_or(&no_tags);
+            (name.as_str(), app_tags.as_slice())
+        })
+        .collect();
+
+    Ok(selector.select(&candidates))
+}
+
+/// Print the summary of a bulk operation and fail if any app failed
+fn finish_bulk(action: &str, outcomes: &[BulkOutcome], output: &Output, https: bool) -> Result<()> {
+    output.bulk_summary(action, outcomes, https);
+
+    let failed = outcomes.iter().filter(|o| !o.is_ok()).count();
+    if failed > 0 {
+        return Err(VulnPkgError::BulkFailed {
+            failed,
+            total: outcomes.len(),
+        });
+    }
     Ok(())
 }
 
+async fn cmd_run_many(
+    manifest: &Manifest,
+    selector: &Selector<'_>,
+    jobs: usize,
+    state_mgr: &StateManager,
+    output: &Output,
+    opts: &StartOptions<'_>,
+) -> Result<()> {
+    let candidates: Vec<(&str, &[String])> = manifest
+        .apps
+        .iter()
+        .map(|app| (app.name.as_str(), app.tags.as_slice()))
+        .collect();
+    let selection = selector.select(&candidates);
+    let apps: Vec<&manifest::App> = selection
+        .matched
+        .iter()
+        .filter_map(|name| manifest.find_app(name))
+        .collect();
+
+    // Start shared infrastructure once so concurrent starts don't race to create Traefik
+    if !apps.is_empty() {
+        let docker = DockerManager::new()?;
+        let needs_proxy = apps.iter().any(|app| !app.http_ports().is_empty());
+        Infrastructure::new(&docker, state_mgr, output)
+            .ensure(opts.domain, opts.https, needs_proxy)
+            .await?;
+    }
+
+    output.info(&format!("Starting {} application(s)", apps.len()));
+    // Owned items keep the stream futures `Send` (needed by `vuln-pkg serve`)
+    let mut outcomes: Vec<BulkOutcome> = futures::stream::iter(apps.into_iter().cloned())
+        .map(|app| async move {
+            match start_app(&app, state_mgr, output, opts).await {
+                Ok((hostnames, allocated_ports)) => BulkOutcome {
+                    hostnames,
+                    allocated_ports,
+                    ..BulkOutcome::ok(&app.name)
+                },
+                Err(e) => BulkOutcome::failed(&app.name, &e),
+            }
+        })
+        .buffered(jobs.max(1))
+        .collect()
+        .await;
+
+    for name in &selection.unmatched {
+        outcomes.push(BulkOutcome::failed(
+            name,
+            &VulnPkgError::AppNotFound(name.clone()),
+        ));
+    }
+
+    finish_bulk("Run", &outcomes, output, opts.https)
+}
+
+async fn cmd_stop_many(
+    selector: &Selector<'_>,
+    tags: &HashMap<String, Vec<String>>,
+    jobs: usize,
+    state_mgr: &StateManager,
+    output: &Output,
+) -> Result<()> {
+    let selection = select_installed(selector, tags, state_mgr, true)?;
+
+    output.info(&format!(
+        "Stopping {} application(s)",
+        selection.matched.len()
+    ));
+    let mut outcomes: Vec<BulkOutcome> = futures::stream::iter(selection.matched.clone())
+        .map(|name| async move {
+            match stop_app(&name, state_mgr, output).await {
+                Ok(()) => BulkOutcome::ok(&name),
+                Err(e) => BulkOutcome::failed(&name, &e),
+            }
+        })
+        .buffered(jobs.max(1))
+        .collect()
+        .await;
+
+    for name in &selection.unmatched {
+        outcomes.push(BulkOutcome::failed(
+            name,
+            &VulnPkgError::AppNotInstalled(name.clone()),
+        ));
+    }
+
+    if outcomes.iter().any(|o| o.is_ok()) {
+        let docker = DockerManager::new()?;
+        Infrastructure::new(&docker, state_mgr, output)
+            .release()
+            .await?;
+    }
+
+    finish_bulk("Stop", &outcomes, output, false)
+}
+
+async fn cmd_remove_many(
+    selector: &Selector<'_>,
+    tags: &HashMap<String, Vec<String>>,
+    jobs: usize,
+    state_mgr: &StateManager,
+    output: &Output,
+    purge: bool,
+) -> Result<()> {
+    let selection = select_installed(selector, tags, state_mgr, false)?;
+
+    output.info(&format!(
+        "Removing {} application(s)",
+        selection.matched.len()
+    ));
+    let mut outcomes: Vec<BulkOutcome> = futures::stream::iter(selection.matched.clone())
+        .map(|name| async move {
+            match remove_app(&name, state_mgr, output, purge).await {
+                Ok(()) => BulkOutcome::ok(&name),
+                Err(e) => BulkOutcome::failed(&name, &e),
+            }
+        })
+        .buffered(jobs.max(1))
+        .collect()
+        .await;
+
+    for name in &selection.unmatched {
+        outcomes.push(BulkOutcome::failed(
+            name,
+            &VulnPkgError::AppNotInstalled(name.clone()),
+        ));
+    }
+
+    if outcomes.iter().any(|o| o.is_ok()) {
+        let docker = DockerManager::new()?;
+        Infrastructure::new(&docker, state_mgr, output)
+            .release()
+            .await?;
+    }
+
+    finish_bulk("Remove", &outcomes, output, false)
+}
+
 async fn cmd_rebuild(
     app_name: &str,
+    no_cache: bool,
+    build_args: &[(String, String)],
     manifest_url: &str,
     state_mgr: &StateManager,
     output: &Output,
@@ -600,50 +1271,13 @@ async fn cmd_rebuild(
     }
 
     let docker = DockerManager::new()?;
-    let effective_image = app.effective_image();
 
     output.info(&format!("Rebuilding {}", app_name));
 
-    // Perform the build based on package type
-    let git_commit = match app.package_type {
-        PackageType::Prebuilt => unreachable!(),
-        PackageType::Dockerfile => {
-            if let Some(ref dockerfile) = app.dockerfile {
-                docker
-                    .build_from_dockerfile(dockerfile, &effective_image, output)
-                    .await?;
-            } else if let Some(ref url) = app.dockerfile_url {
-                docker
-                    .build_from_dockerfile_url(
-                        url,
-                        app.context_url.as_deref(),
-                        &effective_image,
-                        output,
-                    )
-                    .await?;
-            }
-            None
-        }
-        PackageType::Git => {
-            let repo = app.repo.as_ref().ok_or_else(|| {
-                VulnPkgError::ManifestValidation(format!(
-                    "Git app '{}' missing repo field",
-                    app_name
-                ))
-            })?;
-
-            docker
-                .build_from_git(
-                    repo,
-                    app.git_ref.as_deref(),
-                    app.dockerfile_path.as_deref(),
-                    &effective_image,
-                    state_mgr,
-                    output,
-                )
-                .await?
-        }
-    };
+    let mut build = BuildOptions::for_app(app);
+    build.no_cache |= no_cache;
+    build.build_args.extend(build_args.iter().cloned());
+    let git_commit = build_app_image(&docker, app, &build, state_mgr, output).await?;
 
     // Update state with new build timestamp
     let mut state = state_mgr.load_state()?;
@@ -656,6 +1290,133 @@ async fn cmd_rebuild(
     Ok(())
 }
 
+async fn cmd_dev(
+    path: &std::path::Path,
+    options: dev::DevOptions,
+    keep: bool,
+    state_mgr: &StateManager,
+    output: &Output,
+    opts: &StartOptions<'_>,
+) -> Result<()> {
+    let app = dev::dev_app(path, options)?;
+    let root = app.local_dir()?;
+    let dockerfile = app.context_dockerfile()?;
+
+    // Never take over an app installed from a manifest, running or not:
+    // its state would be overwritten and removed again on exit
+    let state = state_mgr.load_state()?;
+    if let Some(app_state) = state.apps.get(&app.name)
+        && app_state.image_source != ImageSource::Local
+    {
+        return Err(VulnPkgError::AppNameTaken(app.name.clone()));
+    }
+
+    output.info(&format!(
+        "Watching {} for changes (Ctrl+C to stop)",
+        root.display()
+    ));
+
+    // Rebuild once the context has stopped changing for one poll, so a
+    // save touching several files triggers a single build
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+    let mut built = None;
+    let mut seen = None;
+    loop {
+        // A scan fails when files vanish under it (an editor's swap file, a
+        // build's output directory); that is a change, so just poll again
+        let current = match BuildContext::new(&root, &dockerfile).and_then(|c| c.fingerprint()) {
+            Ok(current) => Some(current),
+            Err(e) => {
+                if seen.is_some() {
+                    output.warning(&format!("Could not scan {}: {}", root.display(), e));
+                }
+                None
+            }
+        };
+        if let Some(current) = current
+            && built != Some(current)
+            && (built.is_none() || seen == Some(current))
+        {
+            if built.is_some() {
+                output.info("Change detected, rebuilding");
+            }
+            built = Some(current);
+            tokio::select! {
+                _ = &mut interrupted => break,
+                restarted = restart_dev_app(&app, state_mgr, output, opts) => match restarted {
+                    Ok((hostnames, allocated_ports)) => output.app_running(
+                        &app,
+                        &hostnames,
+                        &allocated_ports,
+                        opts.domain,
+                        opts.https,
+                    ),
+                    // Keep watching: the next save may fix the build
+                    Err(e) => output.error(&format!("{} (waiting for changes)", e)),
+                },
+            }
+        }
+        seen = current;
+
+        tokio::select! {
+            _ = &mut interrupted => break,
+            _ = tokio::time::sleep(dev::POLL_INTERVAL) => {}
+        }
+    }
+
+    if keep {
+        output.info(&format!("Leaving {} running", app.name));
+        return Ok(());
+    }
+    if state_mgr.load_state()?.apps.contains_key(&app.name) {
+        remove_app(&app.name, state_mgr, output, false).await?;
+        let docker = DockerManager::new()?;
+        Infrastructure::new(&docker, state_mgr, output)
+            .release()
+            .await?;
+    }
+    output.app_removed(&app.name);
+    Ok(())
+}
+
+/// Build the dev app's image, then replace its container with one running
+/// the new image. The old container keeps serving while the build runs.
+async fn restart_dev_app(
+    app: &manifest::App,
+    state_mgr: &StateManager,
+    output: &Output,
+    opts: &StartOptions<'_>,
+) -> Result<(Vec<String>, Vec<AllocatedPort>)> {
+    install_app(app, state_mgr, output).await?;
+
+    let docker = DockerManager::new()?;
+    if let Some((container_id, running)) = docker.find_app_container(&app.name).await? {
+        if running {
+            docker.stop_container(&container_id).await?;
+        }
+        docker.remove_container(&container_id).await?;
+    }
+    start_app(app, state_mgr, output, opts).await
+}
+
+async fn cmd_outdated(
+    manifest_url: &str,
+    state_mgr: &StateManager,
+    output: &Output,
+    auto_accept: bool,
+) -> Result<()> {
+    let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
+    let state = state_mgr.load_state()?;
+    let docker = DockerManager::new()?;
+    let credentials = GitCredentials::load(&state_mgr.credentials_file())?;
+
+    output.info("Comparing installed apps with their sources");
+    let report = outdated::check_apps(&manifest.apps, &state.apps, &docker, &credentials).await;
+    output.outdated_report(&report);
+    Ok(())
+}
+
 async fn cmd_status(state_mgr: &StateManager, output: &Output) -> Result<()> {
     let state = state_mgr.load_state()?;
     let docker = DockerManager::new()?;
@@ -678,6 +1439,7 @@ async fn cmd_status(state_mgr: &StateManager, output: &Output) -> Result<()> {
             app_state.container_id.clone(),
             app_state.hostnames.clone(),
             app_state.allocated_ports.clone(),
+            app_state.expires_at.clone(),
         ));
     }
 
@@ -685,6 +1447,358 @@ async fn cmd_status(state_mgr: &StateManager, output: &Output) -> Result<()> {
     Ok(())
 }
 
+async fn cmd_gc(
+    state_mgr: &StateManager,
+    output: &Output,
+    dry_run: bool,
+    interval: Option<chrono::TimeDelta>,
+) -> Result<()> {
+    let Some(interval) = interval else {
+        let collected = collect_expired(state_mgr, output, dry_run).await?;
+        output.gc_report(&collected, dry_run);
+        return Ok(());
+    };
+
+    output.info(&format!(
+        "Checking for expired apps every {}s (Ctrl+C to stop)",
+        interval.num_seconds()
+    ));
+    let interval = interval
+        .to_std()
+        .unwrap_or(std::time::Duration::from_secs(60));
+    loop {
+        // A failed sweep (e.g. Docker restarting) must not stop the daemon
+        match collect_expired(state_mgr, output, dry_run).await {
+            Ok(collected) if !collected.is_empty() => output.gc_report(&collected, dry_run),
+            Ok(_) => {}
+            Err(e) => output.error(&format!("Garbage collection failed: {}", e)),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Remove every app whose TTL has run out, returning the apps removed
+/// (or, with `dry_run`, the apps that would be)
+async fn collect_expired(
+    state_mgr: &StateManager,
+    output: &Output,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let expired = ttl::expired_apps(&state_mgr.load_state()?, chrono::Utc::now());
+    if dry_run || expired.is_empty() {
+        return Ok(expired);
+    }
+
+    let mut removed = Vec::new();
+    for name in expired {
+        output.info(&format!("{} has expired, removing it", name));
+        match remove_app(&name, state_mgr, output, false).await {
+            Ok(()) => removed.push(name),
+            Err(e) => output.warning(&format!("Failed to remove {}: {}", name, e)),
+        }
+    }
+
+    if !removed.is_empty() {
+        let docker = DockerManager::new()?;
+        Infrastructure::new(&docker, state_mgr, output)
+            .release()
+            .await?;
+    }
+
+    Ok(removed)
+}
+
+async fn cmd_up(
+    lab_path: &std::path::Path,
+    manifest_url: &str,
+    state_mgr: &StateManager,
+    output: &Output,
+    opts: &StartOptions<'_>,
+    auto_accept: bool,
+) -> Result<()> {
+    let lab = Lab::from_file(lab_path)?;
+    let manifest_url = lab.manifest_url.as_deref().unwrap_or(manifest_url);
+    let manifest = fetch_manifest(manifest_url, state_mgr, output, auto_accept).await?;
+
+    // Resolve everything up front so a typo doesn't leave a half-started lab
+    let apps = lab.resolve(&manifest)?;
+    output.info(&format!(
+        "Bringing up lab {} ({} apps)",
+        lab.name,
+        apps.len()
+    ));
+
+    let docker = DockerManager::new()?;
+
+    let mut missing = Vec::new();
+    for app in &apps {
+        if !docker.image_exists(&app.effective_image()).await? {
+            missing.push(app);
+        }
+    }
+
+    if !missing.is_empty() {
+        output.info(&format!("Installing {} app(s) in parallel", missing.len()));
+        let results = futures::future::join_all(
+            missing
+                .iter()
+                .map(|app| install_app(app, state_mgr, output)),
+        )
+        .await;
+        for result in results {
+            result?;
+        }
+    }
+
+    // Apps start one at a time so port allocation sees previous allocations;
+    // Traefik is started by the first app with HTTP ports and reused after that
+    let mut endpoints: Vec<AppEndpoints> = Vec::new();
+    for app in &apps {
+        match start_app(app, state_mgr, output, opts).await {
+            Ok((hostnames, allocated_ports)) => {
+                endpoints.push((app.name.clone(), hostnames, allocated_ports));
+            }
+            Err(VulnPkgError::AppAlreadyRunning(_)) => {
+                output.info(&format!("{} is already running", app.name));
+                let state = state_mgr.load_state()?;
+                let app_state = state.apps.get(&app.name).cloned().unwrap_or_default();
+                endpoints.push((
+                    app.name.clone(),
+                    app_state.hostnames,
+                    app_state.allocated_ports,
+                ));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    output.lab_up(&lab.name, &endpoints, opts.domain, opts.https);
+    Ok(())
+}
+
+async fn cmd_down(
+    lab_path: &std::path::Path,
+    state_mgr: &StateManager,
+    output: &Output,
+    purge: bool,
+) -> Result<()> {
+    let lab = Lab::from_file(lab_path)?;
+    output.info(&format!("Tearing down lab {}", lab.name));
+
+    let mut removed = Vec::new();
+    for lab_app in &lab.apps {
+        let name = lab_app.name();
+        if !state_mgr.load_state()?.apps.contains_key(name) {
+            output.info(&format!("{} is not installed, skipping", name));
+            continue;
+        }
+        remove_app(name, state_mgr, output, purge).await?;
+        removed.push(name.to_string());
+    }
+
+    if !removed.is_empty() {
+        let docker = DockerManager::new()?;
+        Infrastructure::new(&docker, state_mgr, output)
+            .release()
+            .await?;
+    }
+
+    output.lab_down(&lab.name, &removed);
+    Ok(())
+}
+
+async fn cmd_doctor(
+    state_mgr: &StateManager,
+    output: &Output,
+    ctx: &doctor::DoctorContext<'_>,
+    fix: bool,
+) -> Result<()> {
+    let mut checks = doctor::run_checks(&state_mgr.load_state()?, ctx).await;
+
+    // Repairs need Docker, so they only run once the checks could reach it
+    let docker_ok = checks
+        .iter()
+        .any(|c| c.name == "docker" && c.status == doctor::CheckStatus::Ok);
+    if fix && docker_ok && doctor_fix(state_mgr, output).await? {
+        checks = doctor::run_checks(&state_mgr.load_state()?, ctx).await;
+    }
+    output.doctor_report(&checks);
+
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == doctor::CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(VulnPkgError::DoctorFailed(failed));
+    }
+    Ok(())
+}
+
+/// Remove a stopped Traefik container and reconcile state with Docker.
+/// Returns whether anything was changed.
+async fn doctor_fix(state_mgr: &StateManager, output: &Output) -> Result<bool> {
+    let docker = DockerManager::new()?;
+    let mut state = state_mgr.load_state()?;
+    let mut changed = false;
+
+    if let Some((traefik_id, false)) = docker.find_traefik_container().await? {
+        output.info("Removing stopped vuln-pkg-traefik container");
+        docker.remove_container(&traefik_id).await?;
+        state.traefik_container_id = None;
+        changed = true;
+    }
+
+    let containers = docker.list_managed_containers().await?;
+    let report = reconcile::reconcile(&mut state, &containers);
+    if !report.is_clean() {
+        output.reconcile_report(&report, false);
+        changed = true;
+    }
+
+    if changed {
+        state_mgr.save_state(&state)?;
+    }
+    Ok(changed)
+}
+
+async fn cmd_proxy(
+    command: ProxyCommands,
+    state_mgr: &StateManager,
+    output: &Output,
+    domain: &str,
+    https: bool,
+) -> Result<()> {
+    let docker = DockerManager::new()?;
+    let infra = Infrastructure::new(&docker, state_mgr, output);
+
+    match command {
+        ProxyCommands::Start => {
+            infra.ensure(domain, https, true).await?;
+        }
+        ProxyCommands::Stop => {
+            if docker.count_running_apps().await? > 0 {
+                output.warning("HTTP apps will be unreachable until the proxy is started again");
+            }
+            if !infra.stop_proxy().await? {
+                output.info("Traefik is not running");
+            }
+        }
+        ProxyCommands::Restart => {
+            // Recreated so it picks up the current --domain and --https
+            infra.stop_proxy().await?;
+            infra.ensure(domain, https, true).await?;
+        }
+        ProxyCommands::Status => {}
+    }
+
+    output.proxy_status(&infra.status(domain).await?);
+    Ok(())
+}
+
+fn cmd_cache(command: CacheCommands, state_mgr: &StateManager, output: &Output) -> Result<()> {
+    match command {
+        CacheCommands::Clean {
+            repos,
+            manifests,
+            downloads,
+        } => {
+            // No flag means everything
+            let all = !repos && !manifests && !downloads;
+            let mut cleaned = Vec::new();
+            if repos || all {
+                cleaned.push(state_mgr.clean_repos()?);
+            }
+            if manifests || all {
+                cleaned.push(state_mgr.clean_manifests()?);
+            }
+            if downloads || all {
+                cleaned.push(state_mgr.clean_downloads()?);
+            }
+            output.cache_cleaned(&cleaned);
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_adopt(state_mgr: &StateManager, output: &Output, dry_run: bool) -> Result<()> {
+    let mut state = state_mgr.load_state()?;
+    let docker = DockerManager::new()?;
+
+    output.info("Comparing state with Docker containers");
+    let containers = docker.list_managed_containers().await?;
+    let report = reconcile::reconcile(&mut state, &containers);
+
+    if !dry_run && !report.is_clean() {
+        state_mgr.save_state(&state)?;
+    }
+
+    output.reconcile_report(&report, dry_run);
+    Ok(())
+}
+
+fn cmd_hosts(
+    command: HostsCommands,
+    state_mgr: &StateManager,
+    output: &Output,
+    domain: &str,
+    resolve_address: Ipv4Addr,
+) -> Result<()> {
+    match command {
+        HostsCommands::Sync { dry_run, file } => {
+            hosts_sync(state_mgr, output, domain, resolve_address, file, dry_run)
+        }
+        HostsCommands::Clean { dry_run, file } => {
+            let path = file.unwrap_or_else(hosts::default_hosts_file);
+            let contents = hosts::read_hosts_file(&path)?;
+            let updated = hosts::replace_block(&contents, None)
+                .map_err(|message| hosts::block_error(&path, message))?;
+            let changed = updated.is_some();
+
+            if let Some(updated) = updated
+                && !dry_run
+            {
+                hosts::write_hosts_file(&path, &updated)?;
+            }
+
+            output.hosts_cleaned(&path, changed, dry_run);
+            Ok(())
+        }
+        HostsCommands::Print => {
+            let state = state_mgr.load_state()?;
+            let hostnames = hosts::managed_hostnames(&state, domain);
+            output.hosts_block(&hosts::render_block(resolve_address, &hostnames));
+            Ok(())
+        }
+    }
+}
+
+/// Rewrite the vuln-pkg block of the hosts file from the current state
+fn hosts_sync(
+    state_mgr: &StateManager,
+    output: &Output,
+    domain: &str,
+    resolve_address: Ipv4Addr,
+    file: Option<std::path::PathBuf>,
+    dry_run: bool,
+) -> Result<()> {
+    let path = file.unwrap_or_else(hosts::default_hosts_file);
+    let state = state_mgr.load_state()?;
+    let hostnames = hosts::managed_hostnames(&state, domain);
+    let block = hosts::render_block(resolve_address, &hostnames);
+
+    let contents = hosts::read_hosts_file(&path)?;
+    let updated = hosts::replace_block(&contents, Some(&block))
+        .map_err(|message| hosts::block_error(&path, message))?;
+    if let Some(updated) = updated
+        && !dry_run
+    {
+        hosts::write_hosts_file(&path, &updated)?;
+    }
+
+    output.hosts_synced(&path, &hostnames, &block, dry_run);
+    Ok(())
+}
+
 async fn cmd_manifest(
     command: ManifestCommands,
     manifest_url: &str,
@@ -0,0 +1,134 @@
+use serde::Serialize;
+
+use crate::credentials::GitCredentials;
+use crate::docker::{self, DockerManager};
+use crate::error::VulnPkgError;
+use crate::manifest::{App, PackageType};
+use crate::state::AppState;
+
+/// Whether an installed app matches what its source serves now
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Freshness {
+    UpToDate,
+    Outdated,
+    /// Nothing recorded to compare, or the source could not be reached
+    Unknown,
+}
+
+/// An installed app compared with its git ref or registry image
+#[derive(Debug, Clone, Serialize)]
+pub struct OutdatedApp {
+    pub name: String,
+    /// `git` (commit of the ref) or `image` (registry digest)
+    pub source: &'static str,
+    /// Commit or digest the installed image was made from
+    pub current: Option<String>,
+    /// Commit or digest a rebuild or re-pull would use
+    pub latest: Option<String>,
+    pub status: Freshness,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Compare every installed app that has a checkable source. Dockerfile and
+/// local packages are skipped: their inputs carry no version to compare.
+pub async fn check_apps(
+    apps: &[App],
+    states: &std::collections::HashMap<String, AppState>,
+    docker: &DockerManager,
+    credentials: &GitCredentials,
+) -> Vec<OutdatedApp> {
+    let mut report = Vec::new();
+
+    for app in apps {
+        let Some(state) = states.get(&app.name).filter(|s| s.installed) else {
+            continue;
+        };
+
+        let (source, current, latest) = match app.package_type {
+            PackageType::Dockerfile | PackageType::Local => continue,
+            PackageType::Git => {
+                let repo = app.repo.clone().unwrap_or_default();
+                let git_ref = app.git_ref.clone();
+                let credentials = credentials.clone();
+                // git2 talks to the remote synchronously; keep it off the runtime
+                let lookup = tokio::task::spawn_blocking({
+                    let repo = repo.clone();
+                    move || DockerManager::remote_commit(&repo, git_ref.as_deref(), &credentials)
+                });
+                let latest = lookup.await.unwrap_or_else(|e| {
+                    Err(VulnPkgError::GitClone {
+                        repo,
+                        message: e.to_string(),
+                    })
+                });
+                ("git", Ok(state.git_commit.clone()), latest)
+            }
+            PackageType::Prebuilt => {
+                let image = app.effective_image();
+                (
+                    "image",
+                    docker.image_digest(&image).await,
+                    docker.registry_digest(&image).await,
+                )
+            }
+        };
+
+        report.push(match (current, latest) {
+            (Ok(current), Ok(latest)) => OutdatedApp {
+                name: app.name.clone(),
+                source,
+                status: compare(current.as_deref(), latest.as_deref()),
+                current,
+                latest,
+                error: None,
+            },
+            (Err(e), _) | (_, Err(e)) => OutdatedApp {
+                name: app.name.clone(),
+                source,
+                current: None,
+                latest: None,
+                status: Freshness::Unknown,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+
+    report
+}
+
+/// Compare a recorded revision with the latest one. Commits may be
+/// abbreviated (a ref that is itself a commit SHA), digests never are.
+fn compare(current: Option<&str>, latest: Option<&str>) -> Freshness {
+    match (current, latest) {
+        (Some(current), Some(latest)) if docker::commit_matches(current, latest) => {
+            Freshness::UpToDate
+        }
+        (Some(_), Some(_)) => Freshness::Outdated,
+        _ => Freshness::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let sha = "9fceb02d0ae598e95dc970b74767f19372d61af8";
+        assert_eq!(compare(Some(sha), Some(sha)), Freshness::UpToDate);
+        assert_eq!(compare(Some(sha), Some("9FCEB02")), Freshness::UpToDate);
+        assert_eq!(
+            compare(Some(sha), Some("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678")),
+            Freshness::Outdated
+        );
+        assert_eq!(
+            compare(Some("sha256:aaaa"), Some("sha256:bbbb")),
+            Freshness::Outdated
+        );
+        // Installed before commits were recorded, or pulled without a digest
+        assert_eq!(compare(None, Some(sha)), Freshness::Unknown);
+        assert_eq!(compare(Some(sha), None), Freshness::Unknown);
+    }
+}
@@ -1,8 +1,15 @@
 use colored::Colorize;
 use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
 
+use crate::doctor::{self, Check, CheckStatus};
+use crate::error::VulnPkgError;
+use crate::lifecycle::ProxyStatus;
 use crate::manifest::{App, Manifest, Protocol};
-use crate::state::{AcceptedManifests, AllocatedPort, AppState};
+use crate::outdated::{Freshness, OutdatedApp};
+use crate::reconcile::ReconcileReport;
+use crate::state::{AcceptedManifests, AllocatedPort, AppState, CleanedCache, Endpoint};
+use crate::ttl;
 
 /// Type alias for status info tuple to avoid clippy::type_complexity warning
 pub type StatusInfo = (
@@ -11,62 +18,261 @@ pub type StatusInfo = (
     Option<String>,
     Vec<String>,
     Vec<AllocatedPort>,
+    Option<String>,
 );
 
+/// App name with its HTTP hostnames and allocated TCP/UDP ports
+pub type AppEndpoints = (String, Vec<String>, Vec<AllocatedPort>);
+
+/// Per-app result of a bulk `run`, `stop` or `remove`
+#[derive(Debug, Serialize)]
+pub struct BulkOutcome {
+    pub app: String,
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hostnames: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allocated_ports: Vec<AllocatedPort>,
+}
+
+impl BulkOutcome {
+    pub fn ok(app: &str) -> Self {
+        Self {
+            app: app.to_string(),
+            status: "ok",
+            error: None,
+            hostnames: Vec::new(),
+            allocated_ports: Vec::new(),
+        }
+    }
+
+    pub fn failed(app: &str, error: &VulnPkgError) -> Self {
+        Self {
+            app: app.to_string(),
+            status: "error",
+            error: Some(error.to_string()),
+            hostnames: Vec::new(),
+            allocated_ports: Vec::new(),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Version of the `--json` event schema, sent as `v` on every event.
+/// Bump it when an existing field changes meaning or disappears.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// An event in `--json` mode, printed as one line of NDJSON (or sent to an
+/// API client by `vuln-pkg serve`)
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OutputEvent {
+    /// Machine-readable progress of a long-running step (image pull, build)
+    Progress(Progress),
+    /// A human-readable message (`info`, `success`, `warning`, `error`, `debug`, `build`)
+    Log {
+        level: &'static str,
+        message: String,
+    },
+    /// The command's result; its shape depends on the command
+    Result { data: serde_json::Value },
+    /// The command failed
+    Error { error: ErrorInfo },
+}
+
+/// Progress of an image pull (per layer) or build (per Dockerfile step)
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    /// `pull` or `build`
+    pub action: &'static str,
+    pub image: String,
+    /// Layer ID, for pulls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    /// Docker's status text, e.g. `Downloading` or `Step 2/7 : RUN make`
+    pub status: String,
+    /// Bytes transferred, or the current build step
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<i64>,
+    /// Layer size in bytes, or the number of build steps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorInfo {
+    /// Stable machine-readable code, e.g. `app_not_found`
+    pub code: &'static str,
+    /// Process exit status for this error
+    pub exit_code: i32,
+    pub message: String,
+}
+
+impl From<&VulnPkgError> for ErrorInfo {
+    fn from(error: &VulnPkgError) -> Self {
+        Self {
+            code: error.code(),
+            exit_code: error.exit_code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl OutputEvent {
+    /// The `type` tag, also used as the server-sent event name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OutputEvent::Progress(_) => "progress",
+            OutputEvent::Log { .. } => "log",
+            OutputEvent::Result { .. } => "result",
+            OutputEvent::Error { .. } => "error",
+        }
+    }
+
+    /// Serialize as a single JSON line carrying the schema version
+    pub fn to_json_line(&self) -> String {
+        #[derive(Serialize)]
+        struct Envelope<'a> {
+            v: u32,
+            #[serde(flatten)]
+            event: &'a OutputEvent,
+        }
+
+        serde_json::to_string(&Envelope {
+            v: EVENT_SCHEMA_VERSION,
+            event: self,
+        })
+        .unwrap_or_default()
+    }
+}
+
 pub struct Output {
     json: bool,
+    /// When set, events are sent here instead of stdout (used by `vuln-pkg serve`)
+    sink: Option<UnboundedSender<OutputEvent>>,
 }
 
 impl Output {
     pub fn new(json: bool) -> Self {
-        Self { json }
+        Self { json, sink: None }
+    }
+
+    /// JSON output that is sent to `sink` rather than printed
+    pub fn captured(sink: UnboundedSender<OutputEvent>) -> Self {
+        Self {
+            json: true,
+            sink: Some(sink),
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    pub fn is_captured(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Send an event to the sink, or print it as NDJSON in --json mode
+    fn emit(&self, event: OutputEvent) {
+        if let Some(ref sink) = self.sink {
+            let _ = sink.send(event);
+        } else if self.json {
+            println!("{}", event.to_json_line());
+        }
+    }
+
+    fn log(&self, level: &'static str, msg: &str) {
+        self.emit(OutputEvent::Log {
+            level,
+            message: msg.to_string(),
+        });
     }
 
     pub fn info(&self, msg: &str) {
-        if !self.json {
+        if self.json {
+            self.log("info", msg);
+        } else {
             println!("{} {}", "[*]".blue(), msg);
         }
     }
 
     pub fn success(&self, msg: &str) {
-        if !self.json {
+        if self.json {
+            self.log("success", msg);
+        } else {
             println!("{} {}", "[+]".green(), msg);
         }
     }
 
     pub fn warning(&self, msg: &str) {
-        if !self.json {
+        if self.json {
+            self.log("warning", msg);
+        } else {
             println!("{} {}", "[!]".yellow(), msg);
         }
     }
 
     pub fn error(&self, msg: &str) {
-        if !self.json {
+        if self.json {
+            self.log("error", msg);
+        } else {
             eprintln!("{} {}", "[-]".red(), msg);
         }
     }
 
+    /// Report the error a command failed with
+    pub fn fatal(&self, error: &VulnPkgError) {
+        if self.json {
+            self.emit(OutputEvent::Error {
+                error: error.into(),
+            });
+        } else {
+            eprintln!("{} {}", "[-]".red(), error);
+        }
+    }
+
     pub fn debug(&self, msg: &str) {
-        if !self.json && tracing::enabled!(tracing::Level::DEBUG) {
+        if !tracing::enabled!(tracing::Level::DEBUG) {
+            return;
+        }
+        if self.json {
+            self.log("debug", msg);
+        } else {
             println!("{} {}", "[D]".dimmed(), msg.dimmed());
         }
     }
 
     pub fn build_log(&self, msg: &str) {
-        if !self.json {
+        if self.json {
+            self.log("build", msg);
+        } else {
             println!("{} {}", "   |".dimmed(), msg);
         }
     }
 
+    /// Report pull/build progress (text mode shows it through `build_log`/`debug` instead)
+    pub fn progress(&self, progress: Progress) {
+        if self.json {
+            self.emit(OutputEvent::Progress(progress));
+        }
+    }
+
+    /// Emit the command's result
     pub fn json<T: Serialize>(&self, data: &T) {
         if self.json
-            && let Ok(json) = serde_json::to_string_pretty(data)
+            && let Ok(data) = serde_json::to_value(data)
         {
-            println!("{}", json);
+            self.emit(OutputEvent::Result { data });
         }
     }
 
-    pub fn list_apps(&self, apps: &[App], states: &std::collections::HashMap<String, AppState>) {
+    pub fn list_apps(&self, apps: &[&App], states: &std::collections::HashMap<String, AppState>) {
         if self.json {
             #[derive(Serialize)]
             struct PortInfo {
@@ -120,6 +326,12 @@ impl Output {
             );
             println!();
 
+            if apps.is_empty() {
+                println!("  No applications match the filter");
+                println!();
+                return;
+            }
+
             for app in apps {
                 let state = states.get(&app.name);
                 let status = match state {
@@ -209,25 +421,37 @@ impl Output {
                 container_id: Option<String>,
                 hostnames: Vec<String>,
                 allocated_ports: Vec<AllocatedPortInfo>,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                expires_at: Option<String>,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                remaining_seconds: Option<i64>,
             }
 
+            let now = chrono::Utc::now();
             let info: Vec<StatusInfo> = apps
                 .iter()
                 .map(
-                    |(name, running, container_id, hostnames, allocated_ports)| StatusInfo {
-                        name: name.clone(),
-                        running: *running,
-                        container_id: container_id.clone(),
-                        hostnames: hostnames.clone(),
-                        allocated_ports: allocated_ports
-                            .iter()
-                            .map(|p| AllocatedPortInfo {
-                                container_port: p.container_port,
-                                host_port: p.host_port,
-                                protocol: p.protocol.to_string(),
-                                label: p.label.clone(),
-                            })
-                            .collect(),
+                    |(name, running, container_id, hostnames, allocated_ports, expires_at)| {
+                        StatusInfo {
+                            name: name.clone(),
+                            running: *running,
+                            container_id: container_id.clone(),
+                            hostnames: hostnames.clone(),
+                            allocated_ports: allocated_ports
+                                .iter()
+                                .map(|p| AllocatedPortInfo {
+                                    container_port: p.container_port,
+                                    host_port: p.host_port,
+                                    protocol: p.protocol.to_string(),
+                                    label: p.label.clone(),
+                                })
+                                .collect(),
+                            expires_at: expires_at.clone(),
+                            remaining_seconds: expires_at
+                                .as_deref()
+                                .and_then(ttl::parse_expiry)
+                                .map(|at| (at - now).num_seconds().max(0)),
+                        }
                     },
                 )
                 .collect();
@@ -242,7 +466,8 @@ impl Output {
             println!("\n{}", "Application Status".bold().underline());
             println!();
 
-            for (name, running, container_id, hostnames, allocated_ports) in apps {
+            let now = chrono::Utc::now();
+            for (name, running, container_id, hostnames, allocated_ports, expires_at) in apps {
                 let status = if *running {
                     "RUNNING".green().bold()
                 } else {
@@ -256,6 +481,11 @@ impl Output {
                     println!("    Container: {}", short_id);
                 }
 
+                if let Some(at) = expires_at.as_deref().and_then(ttl::parse_expiry) {
+                    let remaining = ttl::format_remaining(at - now);
+                    println!("    Expires: {}", remaining.yellow());
+                }
+
                 // HTTP endpoints
                 for hostname in hostnames {
                     println!("    URL: {}", format!("http://{}", hostname).cyan());
@@ -280,6 +510,238 @@ impl Output {
         }
     }
 
+    /// Display everything known about one app: manifest definition, build
+    /// metadata from state, local image size and current endpoints
+    pub fn app_info(
+        &self,
+        app: &App,
+        app_state: Option<&AppState>,
+        image_size: Option<i64>,
+        endpoints: &[Endpoint],
+    ) {
+        let port_configs = app.port_configs();
+
+        if self.json {
+            #[derive(Serialize)]
+            struct PortInfo<'a> {
+                port: u16,
+                protocol: String,
+                label: Option<&'a str>,
+            }
+
+            #[derive(Serialize)]
+            struct AppDetails<'a> {
+                name: &'a str,
+                version: &'a str,
+                description: &'a str,
+                #[serde(rename = "type")]
+                package_type: &'a crate::manifest::PackageType,
+                image: String,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                dockerfile_url: Option<&'a str>,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                context_url: Option<&'a str>,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                repo: Option<&'a str>,
+                #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+                git_ref: Option<&'a str>,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                dockerfile_path: Option<&'a str>,
+                ports: Vec<PortInfo<'a>>,
+                env: &'a [String],
+                tags: &'a [String],
+                installed: bool,
+                running: bool,
+                container_id: Option<&'a str>,
+                image_source: Option<&'a crate::state::ImageSource>,
+                image_tag: Option<&'a str>,
+                git_commit: Option<&'a str>,
+                built_at: Option<&'a str>,
+                image_size: Option<i64>,
+                endpoints: &'a [Endpoint],
+                expires_at: Option<&'a str>,
+            }
+
+            self.json(&AppDetails {
+                name: &app.name,
+                version: &app.version,
+                description: &app.description,
+                package_type: &app.package_type,
+                image: app.effective_image(),
+                dockerfile_url: app.dockerfile_url.as_deref(),
+                context_url: app.context_url.as_deref(),
+                repo: app.repo.as_deref(),
+                git_ref: app.git_ref.as_deref(),
+                dockerfile_path: app.dockerfile_path.as_deref(),
+                ports: port_configs
+                    .iter()
+                    .map(|p| PortInfo {
+                        port: p.port,
+                        protocol: p.protocol.to_string(),
+                        label: p.label.as_deref(),
+                    })
+                    .collect(),
+                env: &app.env,
+                tags: &app.tags,
+                installed: app_state.is_some_and(|s| s.installed),
+                running: app_state.is_some_and(|s| s.running),
+                container_id: app_state.and_then(|s| s.container_id.as_deref()),
+                image_source: app_state.map(|s| &s.image_source),
+                image_tag: app_state.and_then(|s| s.image_tag.as_deref()),
+                git_commit: app_state.and_then(|s| s.git_commit.as_deref()),
+                built_at: app_state.and_then(|s| s.built_at.as_deref()),
+                image_size,
+                endpoints,
+                expires_at: app_state.and_then(|s| s.expires_at.as_deref()),
+            });
+            return;
+        }
+
+        let status = match app_state {
+            Some(s) if s.running => "RUNNING".green().bold(),
+            Some(s) if s.installed => "INSTALLED".yellow(),
+            Some(_) => "STOPPED".red(),
+            None => "NOT INSTALLED".dimmed(),
+        };
+        println!();
+        println!("{} {} [{}]", app.name.bold(), app.version.dimmed(), status);
+        if !app.description.is_empty() {
+            println!("  {}", app.description);
+        }
+        println!();
+
+        let field = |name: &str, value: &str| println!("  {:<12} {}", format!("{}:", name), value);
+        let package_type = match app.package_type {
+            crate::manifest::PackageType::Prebuilt => "prebuilt",
+            crate::manifest::PackageType::Dockerfile => "dockerfile",
+            crate::manifest::PackageType::Git => "git",
+            crate::manifest::PackageType::Local => "local",
+        };
+        field("Type", package_type);
+        field("Image", &app.effective_image());
+        if let Some(ref repo) = app.repo {
+            field("Repo", repo);
+            field("Ref", app.git_ref.as_deref().unwrap_or("HEAD"));
+        }
+        if let Some(ref path) = app.dockerfile_path {
+            field("Dockerfile", path);
+        }
+        if let Some(ref url) = app.dockerfile_url {
+            field("Dockerfile", url);
+        } else if app.dockerfile.is_some() {
+            field("Dockerfile", "(inline)");
+        }
+        if let Some(ref url) = app.context_url {
+            field("Context", url);
+        }
+        if !app.tags.is_empty() {
+            field("Tags", &app.tags.join(", "));
+        }
+
+        println!();
+        println!("  {}", "Ports:".bold());
+        for port in &port_configs {
+            println!(
+                "    {:<10} {}",
+                format!("{}/{}", port.port, port.protocol),
+                port.label.as_deref().unwrap_or("").dimmed()
+            );
+        }
+
+        if !app.env.is_empty() {
+            println!();
+            println!("  {}", "Environment:".bold());
+            for var in &app.env {
+                println!("    {}", var);
+            }
+        }
+
+        if let Some(app_state) = app_state {
+            println!();
+            println!("  {}", "Installed:".bold());
+            if let Some(ref tag) = app_state.image_tag {
+                field("  Image tag", tag);
+            }
+            if let Some(ref commit) = app_state.git_commit {
+                field("  Commit", commit);
+            }
+            if let Some(ref built_at) = app_state.built_at {
+                field("  Built at", built_at);
+            }
+            if let Some(ref id) = app_state.container_id {
+                field("  Container", &id[..12.min(id.len())]);
+            }
+            if let Some(at) = app_state.expires_at.as_deref().and_then(ttl::parse_expiry) {
+                field("  Expires", &ttl::format_remaining(at - chrono::Utc::now()));
+            }
+        }
+        if let Some(size) = image_size {
+            field("Image size", &format_size(size));
+        }
+
+        if !endpoints.is_empty() {
+            println!();
+            println!("  {}", "Endpoints:".bold());
+            for endpoint in endpoints {
+                let label = endpoint
+                    .label
+                    .as_ref()
+                    .map(|l| format!(" ({})", l))
+                    .unwrap_or_default();
+                println!(
+                    "    {} {} {}{}",
+                    "->".green(),
+                    format!("[{}]", endpoint.protocol.to_string().to_uppercase()).yellow(),
+                    endpoint.url.cyan(),
+                    label.dimmed()
+                );
+            }
+        }
+        println!();
+    }
+
+    /// Report the URL `open` launched (or could not launch)
+    pub fn app_opened(&self, endpoint: &Endpoint, launched: bool) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "url": endpoint.url,
+                "port": endpoint.port,
+                "label": endpoint.label,
+                "launched": launched,
+            }));
+        } else if launched {
+            self.success(&format!("Opened {}", endpoint.url.cyan()));
+        } else {
+            println!("{}", endpoint.url);
+        }
+    }
+
+    /// Show how to reach a TCP/UDP endpoint
+    pub fn connection_info(&self, endpoint: &Endpoint, command: &str) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "address": endpoint.url,
+                "protocol": endpoint.protocol.to_string(),
+                "port": endpoint.port,
+                "label": endpoint.label,
+                "command": command,
+            }));
+        } else {
+            let label = endpoint
+                .label
+                .as_ref()
+                .map(|l| format!(" ({})", l))
+                .unwrap_or_default();
+            println!(
+                "{} {}{}",
+                format!("[{}]", endpoint.protocol.to_string().to_uppercase()).yellow(),
+                endpoint.url.cyan(),
+                label.dimmed()
+            );
+            println!("  {} {}", "$".dimmed(), command);
+        }
+    }
+
     pub fn app_installed(&self, app: &App) {
         if self.json {
             #[derive(Serialize)]
@@ -383,6 +845,202 @@ impl Output {
         }
     }
 
+    /// Display the per-app summary of a bulk operation
+    pub fn bulk_summary(&self, action: &str, outcomes: &[BulkOutcome], https: bool) {
+        if self.json {
+            self.json(&outcomes);
+            return;
+        }
+
+        println!();
+        println!("{}", format!("{} summary", action).bold().underline());
+        println!();
+
+        let scheme = if https { "https" } else { "http" };
+        for outcome in outcomes {
+            match outcome.error {
+                None => println!("  {} {}", "[+]".green(), outcome.app.bold()),
+                Some(ref error) => println!(
+                    "  {} {} {}",
+                    "[-]".red(),
+                    outcome.app.bold(),
+                    error.dimmed()
+                ),
+            }
+            for hostname in &outcome.hostnames {
+                println!(
+                    "      {} {}",
+                    "->".green(),
+                    format!("{}://{}", scheme, hostname).cyan()
+                );
+            }
+            for alloc in &outcome.allocated_ports {
+                println!(
+                    "      {} {} localhost:{}",
+                    "->".green(),
+                    format!("[{}]", alloc.protocol.to_string().to_uppercase()).yellow(),
+                    alloc.host_port
+                );
+            }
+        }
+
+        let succeeded = outcomes.iter().filter(|o| o.is_ok()).count();
+        println!();
+        println!(
+            "  {} succeeded, {} failed",
+            succeeded.to_string().green(),
+            (outcomes.len() - succeeded).to_string().red()
+        );
+        println!();
+    }
+
+    /// Display the combined endpoint table for a lab
+    pub fn lab_up(&self, lab: &str, apps: &[AppEndpoints], domain: &str, https: bool) {
+        if self.json {
+            #[derive(Serialize)]
+            struct AppInfo<'a> {
+                name: &'a str,
+                hostnames: &'a [String],
+                allocated_ports: &'a [AllocatedPort],
+            }
+
+            #[derive(Serialize)]
+            struct LabResult<'a> {
+                status: &'static str,
+                lab: &'a str,
+                domain: &'a str,
+                https: bool,
+                apps: Vec<AppInfo<'a>>,
+            }
+
+            self.json(&LabResult {
+                status: "up",
+                lab,
+                domain,
+                https,
+                apps: apps
+                    .iter()
+                    .map(|(name, hostnames, allocated_ports)| AppInfo {
+                        name,
+                        hostnames,
+                        allocated_ports,
+                    })
+                    .collect(),
+            });
+            return;
+        }
+
+        self.success(&format!("Lab {} is up", lab.bold()));
+        println!();
+
+        let scheme = if https { "https" } else { "http" };
+        let mut rows: Vec<(String, String, String, String)> = Vec::new();
+        for (name, hostnames, allocated_ports) in apps {
+            for hostname in hostnames {
+                rows.push((
+                    name.clone(),
+                    scheme.to_uppercase(),
+                    format!("{}://{}", scheme, hostname),
+                    String::new(),
+                ));
+            }
+            for alloc in allocated_ports {
+                rows.push((
+                    name.clone(),
+                    alloc.protocol.to_string().to_uppercase(),
+                    format!("{}.{}:{}", name, domain, alloc.host_port),
+                    alloc.label.clone().unwrap_or_default(),
+                ));
+            }
+        }
+
+        let app_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0).max(3);
+        let endpoint_width = rows.iter().map(|r| r.2.len()).max().unwrap_or(0).max(8);
+
+        println!(
+            "  {:<app_width$}  {:<5}  {:<endpoint_width$}  {}",
+            "APP".bold(),
+            "PROTO".bold(),
+            "ENDPOINT".bold(),
+            "LABEL".bold(),
+        );
+        for (name, protocol, endpoint, label) in rows {
+            println!(
+                "  {:<app_width$}  {:<5}  {:<endpoint_width$}  {}",
+                name,
+                protocol.yellow(),
+                endpoint.cyan(),
+                label.dimmed(),
+            );
+        }
+        println!();
+    }
+
+    /// Display the result of tearing down a lab
+    pub fn lab_down(&self, lab: &str, removed: &[String]) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "status": "down",
+                "lab": lab,
+                "removed": removed,
+            }));
+        } else {
+            self.success(&format!(
+                "Lab {} is down ({} app(s) removed)",
+                lab.bold(),
+                removed.len()
+            ));
+        }
+    }
+
+    /// Report the apps removed (or, with --dry-run, due for removal) by `gc`
+    pub fn gc_report(&self, apps: &[String], dry_run: bool) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "status": if dry_run { "dry_run" } else { "collected" },
+                "expired": apps,
+            }));
+        } else if apps.is_empty() {
+            self.info("No expired apps");
+        } else if dry_run {
+            self.info(&format!(
+                "{} expired app(s) would be removed: {}",
+                apps.len(),
+                apps.join(", ")
+            ));
+        } else {
+            self.success(&format!(
+                "Removed {} expired app(s): {}",
+                apps.len(),
+                apps.join(", ")
+            ));
+        }
+    }
+
+    pub fn cache_cleaned(&self, cleaned: &[CleanedCache]) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "status": "cleaned",
+                "caches": cleaned,
+                "bytes": cleaned.iter().map(|c| c.bytes).sum::<u64>(),
+            }));
+            return;
+        }
+
+        for cache in cleaned {
+            if cache.entries == 0 {
+                self.info(&format!("No cached {} to remove", cache.name));
+            } else {
+                self.success(&format!(
+                    "Removed {} cached {} ({})",
+                    cache.entries,
+                    cache.name,
+                    format_size(cache.bytes as i64)
+                ));
+            }
+        }
+    }
+
     pub fn app_stopped(&self, app_name: &str) {
         if self.json {
             #[derive(Serialize)]
@@ -415,6 +1073,325 @@ impl Output {
         }
     }
 
+    /// Display the vuln-pkg hosts block
+    pub fn hosts_block(&self, block: &str) {
+        if self.json {
+            self.json(&serde_json::json!({ "block": block }));
+        } else {
+            print!("{}", block);
+        }
+    }
+
+    /// Display the result of syncing the hosts file
+    pub fn hosts_synced(
+        &self,
+        path: &std::path::Path,
+        hostnames: &[String],
+        block: &str,
+        dry_run: bool,
+    ) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "status": if dry_run { "dry_run" } else { "synced" },
+                "path": path.display().to_string(),
+                "hostnames": hostnames,
+            }));
+        } else if dry_run {
+            self.info(&format!(
+                "Dry run: would write this block to {}",
+                path.display()
+            ));
+            println!();
+            print!("{}", block);
+            println!();
+        } else {
+            self.success(&format!(
+                "Synced {} hostname(s) to {}",
+                hostnames.len(),
+                path.display()
+            ));
+        }
+    }
+
+    /// Display the result of removing the vuln-pkg block from the hosts file
+    pub fn hosts_cleaned(&self, path: &std::path::Path, changed: bool, dry_run: bool) {
+        if self.json {
+            let status = match (changed, dry_run) {
+                (false, _) => "not_found",
+                (true, true) => "dry_run",
+                (true, false) => "cleaned",
+            };
+            self.json(&serde_json::json!({
+                "status": status,
+                "path": path.display().to_string(),
+            }));
+        } else if !changed {
+            self.info(&format!("No vuln-pkg block found in {}", path.display()));
+        } else if dry_run {
+            self.info(&format!(
+                "Dry run: would remove the vuln-pkg block from {}",
+                path.display()
+            ));
+        } else {
+            self.success(&format!(
+                "Removed the vuln-pkg block from {}",
+                path.display()
+            ));
+        }
+    }
+
+    /// Display the state of the Traefik proxy and shared network
+    pub fn proxy_status(&self, status: &ProxyStatus) {
+        if self.json {
+            self.json(status);
+            return;
+        }
+
+        println!();
+        if status.running {
+            println!("  {:<10} {}", "Traefik:".bold(), "running".green());
+            println!("  {:<10} {}", "Dashboard:".bold(), status.dashboard.cyan());
+        } else if status.container_id.is_some() {
+            println!("  {:<10} {}", "Traefik:".bold(), "stopped".yellow());
+        } else {
+            println!("  {:<10} {}", "Traefik:".bold(), "not running".dimmed());
+        }
+        println!(
+            "  {:<10} {}",
+            "Network:".bold(),
+            if status.network {
+                "present".green()
+            } else {
+                "absent".dimmed()
+            }
+        );
+        println!("  {:<10} {} running", "Apps:".bold(), status.running_apps);
+        println!();
+    }
+
+    /// Announce that the API server is accepting requests
+    pub fn api_listening(&self, address: &str, token: Option<&str>) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "status": "listening",
+                "address": address,
+                "token": token,
+            }));
+        } else {
+            self.success(&format!("API listening on {}", address.cyan()));
+            if let Some(token) = token {
+                println!("  {} Authorization: Bearer {}", "->".green(), token);
+            }
+            println!();
+            self.info("Press Ctrl+C to stop");
+        }
+    }
+
+    /// Announce that the DNS responder is serving queries
+    pub fn dns_listening(
+        &self,
+        listen: std::net::SocketAddr,
+        domain: &str,
+        address: std::net::Ipv4Addr,
+        upstream: Option<std::net::SocketAddr>,
+    ) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "status": "listening",
+                "listen": listen.to_string(),
+                "domain": domain,
+                "address": address.to_string(),
+                "upstream": upstream.map(|u| u.to_string()),
+            }));
+        } else {
+            self.success(&format!("DNS server listening on {}", listen));
+            println!(
+                "  {} *.{} -> {}",
+                "->".green(),
+                domain.cyan(),
+                address.to_string().cyan()
+            );
+            match upstream {
+                Some(upstream) => println!("  {} everything else -> {}", "->".green(), upstream),
+                None => println!("  {} everything else -> REFUSED", "->".green()),
+            }
+            println!();
+            self.info("Press Ctrl+C to stop");
+        }
+    }
+
+    /// Display the results of `doctor` checks
+    pub fn doctor_report(&self, checks: &[Check]) {
+        if self.json {
+            #[derive(Serialize)]
+            struct DoctorReport<'a> {
+                healthy: bool,
+                checks: &'a [Check],
+            }
+            self.json(&DoctorReport {
+                healthy: doctor::is_healthy(checks),
+                checks,
+            });
+            return;
+        }
+
+        println!("\n{}", "Environment Diagnostics".bold().underline());
+        println!();
+
+        for check in checks {
+            let status = match check.status {
+                CheckStatus::Ok => "[ OK ]".green().bold(),
+                CheckStatus::Warn => "[WARN]".yellow().bold(),
+                CheckStatus::Fail => "[FAIL]".red().bold(),
+                CheckStatus::Skip => "[SKIP]".dimmed(),
+            };
+            println!("  {} {:<8} {}", status, check.name, check.message);
+            if let Some(ref fix) = check.fix {
+                println!("           {} {}", "fix:".cyan(), fix);
+            }
+        }
+        println!();
+    }
+
+    /// Display how installed apps compare with their git refs and registry images
+    pub fn outdated_report(&self, apps: &[OutdatedApp]) {
+        if self.json {
+            self.json(&serde_json::json!({
+                "outdated": apps.iter().filter(|a| a.status == Freshness::Outdated).count(),
+                "apps": apps,
+            }));
+            return;
+        }
+
+        if apps.is_empty() {
+            println!("No installed git or prebuilt applications to check.");
+            return;
+        }
+
+        // Commits are shortened like `git log --oneline`; digests are kept
+        // long enough to tell apart
+        let short = |value: &Option<String>, source: &str| -> String {
+            match value {
+                Some(v) if source == "git" => v.chars().take(7).collect(),
+                Some(v) => v.chars().take(19).collect(),
+                None => "-".to_string(),
+            }
+        };
+
+        let name_width = apps.iter().map(|a| a.name.len()).max().unwrap_or(0).max(3);
+        println!();
+        println!(
+            "  {:<name_width$}  {:<6}  {:<19}  {:<19}  {}",
+            "APP".bold(),
+            "SOURCE".bold(),
+            "CURRENT".bold(),
+            "LATEST".bold(),
+            "STATUS".bold(),
+        );
+        for app in apps {
+            let status = match app.status {
+                Freshness::UpToDate => "up to date".green(),
+                Freshness::Outdated => "outdated".yellow().bold(),
+                Freshness::Unknown => "unknown".dimmed(),
+            };
+            println!(
+                "  {:<name_width$}  {:<6}  {:<19}  {:<19}  {}",
+                app.name,
+                app.source,
+                short(&app.current, app.source),
+                short(&app.latest, app.source),
+                status,
+            );
+            if let Some(ref error) = app.error {
+                println!("    {} {}", "error:".red(), error);
+            }
+        }
+        println!();
+
+        let outdated: Vec<&OutdatedApp> = apps
+            .iter()
+            .filter(|a| a.status == Freshness::Outdated)
+            .collect();
+        if outdated.is_empty() {
+            self.success("All checked applications are up to date");
+            return;
+        }
+        for app in outdated {
+            let fix = if app.source == "git" {
+                format!("vuln-pkg rebuild {}", app.name)
+            } else {
+                format!(
+                    "vuln-pkg remove --purge {0} && vuln-pkg install {0}",
+                    app.name
+                )
+            };
+            println!("  {} {}", "update:".cyan(), fix);
+        }
+        println!();
+    }
+
+    /// Display the outcome of reconciling state with Docker
+    pub fn reconcile_report(&self, report: &ReconcileReport, dry_run: bool) {
+        if self.json {
+            #[derive(Serialize)]
+            struct ReconcileResult<'a> {
+                status: &'static str,
+                dry_run: bool,
+                #[serde(flatten)]
+                report: &'a ReconcileReport,
+            }
+            self.json(&ReconcileResult {
+                status: if report.is_clean() {
+                    "clean"
+                } else {
+                    "reconciled"
+                },
+                dry_run,
+                report,
+            });
+            return;
+        }
+
+        if report.is_clean() {
+            self.success("State matches Docker, nothing to do");
+            return;
+        }
+
+        let verb = |done: &str, pending: &str| -> String {
+            if dry_run {
+                pending.to_string()
+            } else {
+                done.to_string()
+            }
+        };
+
+        for name in &report.adopted {
+            self.success(&format!(
+                "{} orphaned container for {}",
+                verb("Adopted", "Would adopt"),
+                name.bold()
+            ));
+        }
+        for name in &report.relinked {
+            self.success(&format!(
+                "{} {} to its current container",
+                verb("Relinked", "Would relink"),
+                name.bold()
+            ));
+        }
+        for name in &report.pruned {
+            self.success(&format!(
+                "{} stale container entry for {}",
+                verb("Pruned", "Would prune"),
+                name.bold()
+            ));
+        }
+
+        if dry_run {
+            self.info("Dry run: state was not modified");
+        }
+    }
+
     pub fn search_results(
         &self,
         query: &str,
@@ -562,29 +1539,24 @@ impl Output {
         }
     }
 
-    /// Display manifest information for acceptance prompt
+    /// Display manifest information for acceptance prompt. In JSON mode this
+    /// is a log event, since the command's result comes later.
     pub fn manifest_info(&self, url: &str, manifest: &Manifest) {
         if self.json {
-            #[derive(Serialize)]
-            struct ManifestInfo<'a> {
-                url: &'a str,
-                author: Option<&'a str>,
-                email: Option<&'a str>,
-                manifest_url: Option<&'a str>,
-                description: Option<&'a str>,
-                app_count: usize,
-                apps: Vec<&'a str>,
-            }
-
-            self.json(&ManifestInfo {
-                url,
-                author: manifest.meta.author.as_deref(),
-                email: manifest.meta.email.as_deref(),
-                manifest_url: manifest.meta.url.as_deref(),
-                description: manifest.meta.description.as_deref(),
-                app_count: manifest.apps.len(),
-                apps: manifest.apps.iter().map(|a| a.name.as_str()).collect(),
-            });
+            let mut message = format!("Manifest {}", url);
+            if let Some(ref author) = manifest.meta.author {
+                message.push_str(&format!(" by {}", author));
+                if let Some(ref email) = manifest.meta.email {
+                    message.push_str(&format!(" <{}>", email));
+                }
+            }
+            let apps: Vec<&str> = manifest.apps.iter().map(|a| a.name.as_str()).collect();
+            message.push_str(&format!(
+                " contains {} application(s): {}",
+                apps.len(),
+                apps.join(", ")
+            ));
+            self.log("info", &message);
         } else {
             println!("\n{}", "═".repeat(60).dimmed());
             println!("{}", "  NEW MANIFEST".bold().yellow());
@@ -657,7 +1629,7 @@ impl Output {
     /// Display the raw manifest YAML content
     pub fn show_manifest_yaml(&self, yaml: &str) {
         if self.json {
-            println!("{}", serde_json::json!({ "yaml": yaml }));
+            self.json(&serde_json::json!({ "yaml": yaml }));
         } else {
             println!("\n{}", "Manifest Contents:".bold().underline());
             println!("{}", "─".repeat(60).dimmed());
@@ -720,3 +1692,58 @@ impl Output {
         }
     }
 }
+
+/// Format a byte count with binary units, e.g. `412.3 MiB`
+fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_lines_carry_version_and_type() {
+        let line = OutputEvent::Log {
+            level: "info",
+            message: "hello".to_string(),
+        }
+        .to_json_line();
+        assert_eq!(
+            line,
+            r#"{"v":1,"type":"log","level":"info","message":"hello"}"#
+        );
+
+        let line = OutputEvent::Progress(Progress {
+            action: "build",
+            image: "vuln-pkg/lab:1.0".to_string(),
+            layer: None,
+            status: "Step 2/5 : RUN make".to_string(),
+            current: Some(2),
+            total: Some(5),
+        })
+        .to_json_line();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["type"], "progress");
+        assert_eq!(value["current"], 2);
+        assert!(value.get("layer").is_none());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(432_340_000), "412.3 MiB");
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+
+use crate::manifest::{App, PackageType, Protocol};
+use crate::state::AppState;
+
+/// A parsed `search` / `list --filter` query: free-text terms plus
+/// field filters such as `tag:web`, `type:git` or `cve:2017`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    raw: String,
+    terms: Vec<String>,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Tag(String),
+    Type(PackageType),
+    Protocol(Protocol),
+    Installed(bool),
+    Running(bool),
+    Cve(String),
+}
+
+/// Result ordering for `search` and `list`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Best match first (manifest order for `list` without search terms)
+    #[default]
+    Relevance,
+    /// Alphabetical by app name
+    Name,
+    /// Running apps first, then installed, then available
+    Status,
+}
+
+/// Parse a query, rejecting invalid filter values. Tokens whose key is not a
+/// known filter (e.g. `http://host`) are searched for as free text.
+pub fn parse_query(input: &str) -> Result<Query, String> {
+    let mut query = Query {
+        raw: input.trim().to_string(),
+        ..Default::default()
+    };
+
+    for token in input.split_whitespace() {
+        let Some((key, value)) = token.split_once(':') else {
+            query.terms.push(token.to_lowercase());
+            continue;
+        };
+        let key = key.to_lowercase();
+        if !matches!(
+            key.as_str(),
+            "tag" | "type" | "protocol" | "installed" | "running" | "cve"
+        ) {
+            query.terms.push(token.to_lowercase());
+            continue;
+        }
+        if value.is_empty() {
+            return Err(format!("filter '{}' needs a value", token));
+        }
+
+        let value = value.to_lowercase();
+        let filter = match key.as_str() {
+            "tag" => Filter::Tag(value),
+            "type" => Filter::Type(match value.as_str() {
+                "prebuilt" => PackageType::Prebuilt,
+                "dockerfile" => PackageType::Dockerfile,
+                "git" => PackageType::Git,
+                "local" => PackageType::Local,
+                _ => {
+                    return Err(format!(
+                        "invalid type '{}' (expected prebuilt, dockerfile, git or local)",
+                        value
+                    ));
+                }
+            }),
+            "protocol" => Filter::Protocol(match value.as_str() {
+                "http" => Protocol::Http,
+                "tcp" => Protocol::Tcp,
+                "udp" => Protocol::Udp,
+                _ => {
+                    return Err(format!(
+                        "invalid protocol '{}' (expected http, tcp or udp)",
+                        value
+                    ));
+                }
+            }),
+            "installed" => Filter::Installed(parse_bool(&key, &value)?),
+            "running" => Filter::Running(parse_bool(&key, &value)?),
+            "cve" => Filter::Cve(value.trim_start_matches("cve-").to_string()),
+            _ => unreachable!("not a filter key: {}", key),
+        };
+        query.filters.push(filter);
+    }
+
+    Ok(query)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" => Ok(true),
+        "false" | "no" => Ok(false),
+        _ => Err(format!("{}: expects true or false, got '{}'", key, value)),
+    }
+}
+
+impl Query {
+    /// The query as the user typed it
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Relevance of `app`, or None if it does not match. Every filter and
+    /// every term has to match; apps matching only filters score 0.
+    pub fn score(&self, app: &App, state: Option<&AppState>) -> Option<u32> {
+        if !self.filters.iter().all(|f| f.matches(app, state)) {
+            return None;
+        }
+        self.terms
+            .iter()
+            .map(|term| term_score(term, app))
+            .sum::<Option<u32>>()
+    }
+
+    /// Matching apps, ordered by `sort`
+    pub fn apply<'a>(
+        &self,
+        apps: &'a [App],
+        states: &HashMap<String, AppState>,
+        sort: SortKey,
+    ) -> Vec<&'a App> {
+        let mut matches: Vec<(&App, u32)> = apps
+            .iter()
+            .filter_map(|app| {
+                self.score(app, states.get(&app.name))
+                    .map(|score| (app, score))
+            })
+            .collect();
+
+        // Stable sorts, so ties keep manifest order
+        match sort {
+            SortKey::Relevance => matches.sort_by_key(|(_, score)| std::cmp::Reverse(*score)),
+            SortKey::Name => matches.sort_by(|a, b| a.0.name.cmp(&b.0.name)),
+            SortKey::Status => matches.sort_by_key(|(app, _)| match states.get(&app.name) {
+                Some(s) if s.running => 0,
+                Some(s) if s.installed => 1,
+                _ => 2,
+            }),
+        }
+
+        matches.into_iter().map(|(app, _)| app).collect()
+    }
+}
+
+impl Filter {
+    fn matches(&self, app: &App, state: Option<&AppState>) -> bool {
+        match self {
+            Filter::Tag(tag) => app.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            Filter::Type(package_type) => app.package_type == *package_type,
+            Filter::Protocol(protocol) => {
+                app.port_configs().iter().any(|p| p.protocol == *protocol)
+            }
+            Filter::Installed(wanted) => state.is_some_and(|s| s.installed) == *wanted,
+            Filter::Running(wanted) => state.is_some_and(|s| s.running) == *wanted,
+            Filter::Cve(id) => app.tags.iter().any(|t| {
+                t.to_lowercase()
+                    .strip_prefix("cve-")
+                    .is_some_and(|rest| rest.starts_with(id.as_str()))
+            }),
+        }
+    }
+}
+
+/// Best score of a (lowercase) term against the app's fields
+fn term_score(term: &str, app: &App) -> Option<u32> {
+    let name = app.name.to_lowercase();
+    let name_score = if name == term {
+        100
+    } else if name.starts_with(term) {
+        80
+    } else if name.contains(term) {
+        60
+    } else {
+        0
+    };
+
+    let tag_score = app
+        .tags
+        .iter()
+        .map(|t| {
+            let t = t.to_lowercase();
+            if t == term {
+                50
+            } else if t.contains(term) {
+                40
+            } else {
+                0
+            }
+        })
+        .max()
+        .unwrap_or(0);
+
+    let description_score = if app.description.to_lowercase().contains(term) {
+        20
+    } else {
+        0
+    };
+
+    let score = name_score
+        .max(tag_score)
+        .max(description_score)
+        .max(fuzzy_score(term, &name));
+    (score > 0).then_some(score)
+}
+
+/// Score a typo-tolerant subsequence match (`dvw` in `dvwa`, `jsp` in
+/// `juice-shop`): 1-15, higher when the matched characters are close together.
+/// Positions and lengths are counted in chars, not bytes.
+fn fuzzy_score(term: &str, text: &str) -> u32 {
+    let term_len = term.chars().count();
+    if term_len < 2 {
+        return 0;
+    }
+
+    let mut chars = text.chars().enumerate();
+    let mut first = None;
+    let mut last = 0;
+    for wanted in term.chars() {
+        match chars.find(|(_, c)| *c == wanted) {
+            Some((i, _)) => {
+                first.get_or_insert(i);
+                last = i;
+            }
+            None => return 0,
+        }
+    }
+
+    let span = last - first.unwrap_or(0) + 1;
+    let gaps = span - term_len;
+    // Letters scattered across the whole name are noise, not a match
+    if gaps > term_len * 2 {
+        return 0;
+    }
+    15u32.saturating_sub(gaps as u32 * 2).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+
+    fn manifest() -> Manifest {
+        Manifest::parse(
+            r#"
+apps:
+  - name: dvwa
+    version: "1.0"
+    image: vulnerables/web-dvwa
+    ports: [80]
+    tags: [web, php, sqli]
+    description: "Damn Vulnerable Web Application"
+  - name: struts2-showcase
+    version: "2.3"
+    image: vulhub/struts2
+    ports: [8080]
+    tags: [web, java, CVE-2017-5638]
+    description: "Apache Struts RCE"
+  - name: mysql-weak
+    version: "5.7"
+    type: git
+    repo: https://example.com/mysql-weak.git
+    ports:
+      - port: 3306
+        protocol: tcp
+    tags: [database]
+    description: "MySQL with weak credentials, used by the sqli labs"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn names(query: &str, states: &HashMap<String, AppState>, sort: SortKey) -> Vec<String> {
+        let manifest = manifest();
+        parse_query(query)
+            .unwrap()
+            .apply(&manifest.apps, states, sort)
+            .into_iter()
+            .map(|app| app.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("sql Tag:Web type:git").unwrap();
+        assert_eq!(query.terms, vec!["sql"]);
+        assert_eq!(
+            query.filters,
+            vec![
+                Filter::Tag("web".to_string()),
+                Filter::Type(PackageType::Git)
+            ]
+        );
+        assert_eq!(query.as_str(), "sql Tag:Web type:git");
+
+        assert!(parse_query("type:zip").is_err());
+        assert!(parse_query("installed:maybe").is_err());
+        assert!(parse_query("tag:").is_err());
+
+        // Unknown keys are plain search terms
+        let query = parse_query("author:me http://Example.com tag:web").unwrap();
+        assert_eq!(query.terms, vec!["author:me", "http://example.com"]);
+        assert_eq!(query.filters, vec![Filter::Tag("web".to_string())]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let none = HashMap::new();
+        assert_eq!(
+            names("tag:web", &none, SortKey::Relevance),
+            vec!["dvwa", "struts2-showcase"]
+        );
+        assert_eq!(
+            names("type:git", &none, SortKey::Relevance),
+            vec!["mysql-weak"]
+        );
+        assert_eq!(
+            names("protocol:tcp", &none, SortKey::Relevance),
+            vec!["mysql-weak"]
+        );
+        assert_eq!(
+            names("cve:2017", &none, SortKey::Relevance),
+            vec!["struts2-showcase"]
+        );
+        assert_eq!(
+            names("cve:CVE-2017-5638", &none, SortKey::Relevance),
+            vec!["struts2-showcase"]
+        );
+        assert!(names("cve:2018", &none, SortKey::Relevance).is_empty());
+
+        let mut states = HashMap::new();
+        states.insert(
+            "mysql-weak".to_string(),
+            AppState {
+                installed: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            names("installed:true", &states, SortKey::Relevance),
+            vec!["mysql-weak"]
+        );
+        assert_eq!(
+            names("installed:false tag:web", &states, SortKey::Relevance).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_ranking() {
+        let none = HashMap::new();
+        // Tag match on dvwa beats a description match on mysql-weak
+        assert_eq!(
+            names("sqli", &none, SortKey::Relevance),
+            vec!["dvwa", "mysql-weak"]
+        );
+        // Every term has to match
+        assert_eq!(
+            names("web java", &none, SortKey::Relevance),
+            vec!["struts2-showcase"]
+        );
+        // Subsequence match tolerates missing characters
+        assert_eq!(
+            names("strts", &none, SortKey::Relevance),
+            vec!["struts2-showcase"]
+        );
+        assert_eq!(
+            names("", &none, SortKey::Name),
+            vec!["dvwa", "mysql-weak", "struts2-showcase"]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_status() {
+        let mut states = HashMap::new();
+        for (name, running) in [("struts2-showcase", true), ("mysql-weak", false)] {
+            states.insert(
+                name.to_string(),
+                AppState {
+                    installed: true,
+                    running,
+                    ..Default::default()
+                },
+            );
+        }
+        assert_eq!(
+            names("", &states, SortKey::Status),
+            vec!["struts2-showcase", "mysql-weak", "dvwa"]
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("dvw", "dvwa") > fuzzy_score("dwa", "dvwa"));
+        assert_eq!(fuzzy_score("xyz", "dvwa"), 0);
+        assert_eq!(fuzzy_score("ao", "a-very-long-name-o"), 0);
+        // Multibyte characters count once
+        assert_eq!(fuzzy_score("aé", "aé"), 15);
+        assert_eq!(fuzzy_score("çé", "ç-é"), 13);
+        assert_eq!(fuzzy_score("é", "é"), 0);
+    }
+}
@@ -0,0 +1,167 @@
+use serde::Serialize;
+
+use crate::docker::ManagedContainer;
+use crate::state::{AppState, State};
+
+/// Changes made (or proposed) when reconciling state with Docker
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconcileReport {
+    /// Apps whose container was found in Docker but not in state
+    pub adopted: Vec<String>,
+    /// Apps whose state pointed at a different container than Docker has
+    pub relinked: Vec<String>,
+    /// Apps whose recorded container no longer exists
+    pub pruned: Vec<String>,
+}
+
+impl ReconcileReport {
+    /// True when state already matched Docker
+    pub fn is_clean(&self) -> bool {
+        self.adopted.is_empty() && self.relinked.is_empty() && self.pruned.is_empty()
+    }
+}
+
+/// Bring `state` in line with the vuln-pkg containers Docker actually has.
+///
+/// Orphaned containers are re-imported with their hostnames and host ports,
+/// and entries whose container was deleted lose their container ID and port
+/// allocations (the app stays installed, since its image may still exist).
+pub fn reconcile(state: &mut State, containers: &[ManagedContainer]) -> ReconcileReport {
+    let mut report = ReconcileReport::default();
+
+    for container in containers {
+        match state.apps.get_mut(&container.app) {
+            None => {
+                state.apps.insert(
+                    container.app.clone(),
+                    AppState {
+                        installed: true,
+                        running: container.running,
+                        container_id: Some(container.id.clone()),
+                        hostnames: container.hostnames.clone(),
+                        image_tag: container.image.clone(),
+                        allocated_ports: container.allocated_ports.clone(),
+                        ..Default::default()
+                    },
+                );
+                report.adopted.push(container.app.clone());
+            }
+            Some(app_state) if app_state.container_id.as_deref() != Some(&container.id) => {
+                app_state.installed = true;
+                app_state.running = container.running;
+                app_state.container_id = Some(container.id.clone());
+                app_state.hostnames = container.hostnames.clone();
+                app_state.allocated_ports = container.allocated_ports.clone();
+                report.relinked.push(container.app.clone());
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (name, app_state) in state.apps.iter_mut() {
+        let Some(ref container_id) = app_state.container_id else {
+            continue;
+        };
+        if containers.iter().any(|c| &c.id == container_id) {
+            continue;
+        }
+
+        app_state.running = false;
+        app_state.container_id = None;
+        app_state.hostnames.clear();
+        app_state.allocated_ports.clear();
+        report.pruned.push(name.clone());
+    }
+
+    report.adopted.sort();
+    report.relinked.sort();
+    report.pruned.sort();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Protocol;
+    use crate::state::AllocatedPort;
+
+    fn container(id: &str, app: &str) -> ManagedContainer {
+        ManagedContainer {
+            id: id.to_string(),
+            app: app.to_string(),
+            running: true,
+            image: Some("mongo:8.0.16".to_string()),
+            hostnames: Vec::new(),
+            allocated_ports: vec![AllocatedPort {
+                container_port: 27017,
+                host_port: 40000,
+                protocol: Protocol::Tcp,
+                label: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_adopts_orphaned_container() {
+        let mut state = State::new();
+        let report = reconcile(&mut state, &[container("abc", "mongo")]);
+
+        assert_eq!(report.adopted, vec!["mongo"]);
+        let app = &state.apps["mongo"];
+        assert!(app.installed && app.running);
+        assert_eq!(app.container_id.as_deref(), Some("abc"));
+        assert_eq!(state.allocated_host_ports(), vec![40000]);
+    }
+
+    #[test]
+    fn test_prunes_dangling_entry() {
+        let mut state = State::new();
+        state.apps.insert(
+            "dvwa".to_string(),
+            AppState {
+                installed: true,
+                running: true,
+                container_id: Some("gone".to_string()),
+                hostnames: vec!["dvwa.lab.local".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let report = reconcile(&mut state, &[]);
+
+        assert_eq!(report.pruned, vec!["dvwa"]);
+        let app = &state.apps["dvwa"];
+        assert!(app.installed);
+        assert!(!app.running);
+        assert!(app.container_id.is_none());
+        assert!(app.hostnames.is_empty());
+    }
+
+    #[test]
+    fn test_relinks_recreated_container() {
+        let mut state = State::new();
+        state.apps.insert(
+            "mongo".to_string(),
+            AppState {
+                installed: true,
+                container_id: Some("old".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let report = reconcile(&mut state, &[container("new", "mongo")]);
+
+        assert_eq!(report.relinked, vec!["mongo"]);
+        assert!(report.pruned.is_empty());
+        assert_eq!(state.apps["mongo"].container_id.as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn test_consistent_state_is_clean() {
+        let mut state = State::new();
+        let containers = [container("abc", "mongo")];
+        reconcile(&mut state, &containers);
+
+        assert!(reconcile(&mut state, &containers).is_clean());
+    }
+}
@@ -0,0 +1,160 @@
+/// Picks apps for bulk commands from explicit names, glob patterns, tags or `--all`
+pub struct Selector<'a> {
+    pub patterns: &'a [String],
+    pub tags: &'a [String],
+    pub all: bool,
+}
+
+/// Result of applying a selector to a set of candidate apps
+#[derive(Debug, Default, PartialEq)]
+pub struct Selection {
+    /// Matching app names, in candidate order
+    pub matched: Vec<String>,
+    /// Names or patterns that matched nothing
+    pub unmatched: Vec<String>,
+}
+
+impl Selector<'_> {
+    /// A single plain app name keeps the original single-app behaviour;
+    /// anything else is a bulk operation with a per-app summary
+    pub fn is_bulk(&self) -> bool {
+        self.all || !self.tags.is_empty() || self.patterns.len() != 1 || is_glob(&self.patterns[0])
+    }
+
+    /// Select from `(name, tags)` candidates
+    pub fn select(&self, candidates: &[(&str, &[String])]) -> Selection {
+        let mut selection = Selection::default();
+
+        for (name, tags) in candidates {
+            let by_name = self.patterns.iter().any(|p| glob_match(p, name));
+            let by_tag = self
+                .tags
+                .iter()
+                .any(|wanted| tags.iter().any(|t| t.eq_ignore_ascii_case(wanted)));
+
+            if (self.all || by_name || by_tag) && !selection.matched.iter().any(|m| m == name) {
+                selection.matched.push(name.to_string());
+            }
+        }
+
+        for pattern in self.patterns {
+            if !candidates.iter().any(|(name, _)| glob_match(pattern, name)) {
+                selection.unmatched.push(pattern.clone());
+            }
+        }
+
+        selection
+    }
+}
+
+/// Whether `pattern` contains glob characters
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Match `text` against a shell-style pattern supporting `*` and `?`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` swallow one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("dvwa", "dvwa"));
+        assert!(!glob_match("dvwa", "dvwa2"));
+        assert!(glob_match("web*", "webgoat"));
+        assert!(glob_match("*shop", "juice-shop"));
+        assert!(glob_match("*o*o*", "mongobleed"));
+        assert!(glob_match("vamp?", "vampi"));
+        assert!(!glob_match("vamp?", "vamp"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_select_by_name_glob_and_tag() {
+        let owasp = strings(&["OWASP-Top-10"]);
+        let none: Vec<String> = Vec::new();
+        let candidates: Vec<(&str, &[String])> = vec![
+            ("dvwa", &owasp),
+            ("juice-shop", &owasp),
+            ("webgoat", &none),
+            ("mongobleed", &none),
+        ];
+
+        let patterns = strings(&["web*", "missing"]);
+        let tags = strings(&["owasp-top-10"]);
+        let selection = Selector {
+            patterns: &patterns,
+            tags: &tags,
+            all: false,
+        }
+        .select(&candidates);
+
+        assert_eq!(selection.matched, vec!["dvwa", "juice-shop", "webgoat"]);
+        assert_eq!(selection.unmatched, vec!["missing"]);
+    }
+
+    #[test]
+    fn test_select_all() {
+        let none: Vec<String> = Vec::new();
+        let candidates: Vec<(&str, &[String])> = vec![("dvwa", &none), ("vampi", &none)];
+        let selection = Selector {
+            patterns: &[],
+            tags: &[],
+            all: true,
+        }
+        .select(&candidates);
+        assert_eq!(selection.matched, vec!["dvwa", "vampi"]);
+        assert!(selection.unmatched.is_empty());
+    }
+
+    #[test]
+    fn test_is_bulk() {
+        let single = strings(&["dvwa"]);
+        let many = strings(&["dvwa", "vampi"]);
+        let glob = strings(&["web*"]);
+        let selector = |patterns: &[String], all: bool| {
+            Selector {
+                patterns,
+                tags: &[],
+                all,
+            }
+            .is_bulk()
+        };
+
+        assert!(!selector(&single, false));
+        assert!(selector(&many, false));
+        assert!(selector(&glob, false));
+        assert!(selector(&[], true));
+    }
+}
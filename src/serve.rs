@@ -0,0 +1,504 @@
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use http_body_util::{BodyExt, Full, StreamBody, combinators::BoxBody};
+use hyper::body::{Bytes, Frame, Incoming};
+use hyper::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{Mutex, mpsc};
+
+use crate::cli::{BulkArgs, Cli, Commands};
+use crate::error::{Result, VulnPkgError};
+use crate::output::{EVENT_SCHEMA_VERSION, Output, OutputEvent};
+use crate::query::{SortKey, parse_query};
+use crate::select::is_glob;
+
+type Body = BoxBody<Bytes, Infallible>;
+
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// Runs one CLI command with the given output (`run` in main.rs)
+pub type Handler = fn(Cli, Output) -> HandlerFuture;
+
+/// Where the API listens
+pub enum Listen {
+    /// 127.0.0.1 (or another address); every request must carry the token
+    Tcp { addr: SocketAddr, token: String },
+    /// A unix socket; access is controlled by file permissions
+    Unix(PathBuf),
+}
+
+/// Global flags every API request runs with (taken from the `serve` invocation)
+pub struct Globals {
+    pub yes: bool,
+    pub manifest_url: String,
+    pub resolve_address: Ipv4Addr,
+    pub domain: Option<String>,
+    pub https: bool,
+}
+
+struct Api {
+    globals: Globals,
+    handler: Handler,
+    token: Option<String>,
+    /// Held while a request that changes apps runs, so concurrent
+    /// install/run/stop/remove requests don't overwrite each other's state
+    writes: Arc<Mutex<()>>,
+}
+
+/// Serve the API until the process is interrupted
+pub async fn serve(
+    listen: Listen,
+    globals: Globals,
+    handler: Handler,
+    output: &Output,
+) -> Result<()> {
+    match listen {
+        Listen::Tcp { addr, token } => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .map_err(|e| VulnPkgError::ApiServer(format!("Failed to bind {}: {}", addr, e)))?;
+            if !addr.ip().is_loopback() {
+                output.warning(&format!(
+                    "{} is reachable from other machines; anyone with the token controls Docker",
+                    addr
+                ));
+            }
+            output.api_listening(&format!("http://{}", addr), Some(&token));
+
+            let api = Arc::new(Api {
+                globals,
+                handler,
+                token: Some(token),
+                writes: Arc::default(),
+            });
+            loop {
+                let (stream, _) = listener.accept().await?;
+                spawn_connection(stream, Arc::clone(&api));
+            }
+        }
+        Listen::Unix(path) => serve_unix(path, globals, handler, output).await,
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix(
+    path: PathBuf,
+    globals: Globals,
+    handler: Handler,
+    output: &Output,
+) -> Result<()> {
+    let listener = bind_private_socket(&path).map_err(|e| {
+        VulnPkgError::ApiServer(format!("Failed to bind {}: {}", path.display(), e))
+    })?;
+    output.api_listening(&format!("unix:{}", path.display()), None);
+
+    let api = Arc::new(Api {
+        globals,
+        handler,
+        token: None,
+        writes: Arc::default(),
+    });
+    loop {
+        let (stream, _) = listener.accept().await?;
+        spawn_connection(stream, Arc::clone(&api));
+    }
+}
+
+/// Bind a unix socket that only the current user can connect to. The socket
+/// is created inside a fresh 0700 directory and made 0600 there, then moved
+/// into place (replacing one left over from a previous run), so it is never
+/// reachable with looser permissions.
+#[cfg(unix)]
+fn bind_private_socket(path: &std::path::Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file path"))?;
+    let staging = path.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+
+    let staged = staging.join("api.sock");
+    let bound = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+    bound
+}
+
+#[cfg(not(unix))]
+async fn serve_unix(
+    _path: PathBuf,
+    _globals: Globals,
+    _handler: Handler,
+    _output: &Output,
+) -> Result<()> {
+    Err(VulnPkgError::ApiServer(
+        "Unix sockets are not supported on this platform; use --listen".to_string(),
+    ))
+}
+
+fn spawn_connection<S>(stream: S, api: Arc<Api>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let service = service_fn(move |req| handle(req, Arc::clone(&api)));
+        if let Err(e) = http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), service)
+            .await
+        {
+            tracing::debug!("API connection error: {}", e);
+        }
+    });
+}
+
+async fn handle(
+    req: Request<Incoming>,
+    api: Arc<Api>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    if let Some(ref token) = api.token
+        && !authorized(&req, token)
+    {
+        return Ok(json_response(
+            StatusCode::UNAUTHORIZED,
+            &serde_json::json!({ "error": "missing or invalid bearer token" }),
+        ));
+    }
+
+    let command = match route(req.method(), req.uri().path(), req.uri().query()) {
+        Ok(command) => command,
+        Err(status) => {
+            return Ok(json_response(
+                status,
+                &serde_json::json!({ "error": status.canonical_reason() }),
+            ));
+        }
+    };
+
+    let stream = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"));
+
+    Ok(execute(&api, command, stream).await)
+}
+
+fn authorized(req: &Request<Incoming>, token: &str) -> bool {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Map a request onto the CLI command it stands for
+fn route(
+    method: &Method,
+    path: &str,
+    query: Option<&str>,
+) -> std::result::Result<Commands, StatusCode> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    let command = match (method, segments.as_slice()) {
+        (&Method::GET, ["apps"]) => Commands::List {
+            filter: match query_param(query, "filter") {
+                Some(filter) => Some(parse_query(&filter).map_err(|_| StatusCode::BAD_REQUEST)?),
+                None => None,
+            },
+            sort: sort_param(query)?,
+        },
+        (&Method::GET, ["search"]) => Commands::Search {
+            query: parse_query(&query_param(query, "q").unwrap_or_default())
+                .map_err(|_| StatusCode::BAD_REQUEST)?,
+            sort: sort_param(query)?,
+        },
+        (&Method::GET, ["status"]) => Commands::Status,
+        (&Method::POST, ["apps", app, action]) => {
+            let app = percent_decode(app);
+            // One app per request: a pattern would turn it into a bulk operation
+            if is_glob(&app) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            match *action {
+                "install" => Commands::Install { app },
+                "run" => Commands::Run {
+                    apps: vec![app],
+                    bulk: single_app(),
+                    ttl: match query_param(query, "ttl") {
+                        Some(ttl) => {
+                            Some(crate::ttl::parse_ttl(&ttl).map_err(|_| StatusCode::BAD_REQUEST)?)
+                        }
+                        None => None,
+                    },
+                },
+                "stop" => Commands::Stop {
+                    apps: vec![app],
+                    bulk: single_app(),
+                },
+                "remove" => Commands::Remove {
+                    apps: vec![app],
+                    purge: query_param(query, "purge").is_some_and(|v| v == "true"),
+                    bulk: single_app(),
+                },
+                _ => return Err(StatusCode::NOT_FOUND),
+            }
+        }
+        (_, ["apps"] | ["search"] | ["status"] | ["apps", _, _]) => {
+            return Err(StatusCode::METHOD_NOT_ALLOWED);
+        }
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+
+    Ok(command)
+}
+
+fn single_app() -> BulkArgs {
+    BulkArgs {
+        tag: Vec::new(),
+        all: false,
+        jobs: 1,
+    }
+}
+
+fn sort_param(query: Option<&str>) -> std::result::Result<SortKey, StatusCode> {
+    match query_param(query, "sort") {
+        Some(sort) => SortKey::from_str(&sort, true).map_err(|_| StatusCode::BAD_REQUEST),
+        None => Ok(SortKey::default()),
+    }
+}
+
+fn query_param(query: Option<&str>, name: &str) -> Option<String> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(&value.replace('+', " ")))
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = input.get(i + 1..i + 3)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Run a command through the regular CLI code path with its output captured
+async fn execute(api: &Api, command: Commands, stream: bool) -> Response<Body> {
+    let globals = &api.globals;
+    let cli = Cli {
+        json: true,
+        yes: globals.yes,
+        manifest_url: globals.manifest_url.clone(),
+        resolve_address: globals.resolve_address,
+        domain: globals.domain.clone(),
+        https: globals.https,
+        sync_hosts: false,
+        command,
+    };
+
+    let writes = changes_apps(&cli.command).then(|| Arc::clone(&api.writes));
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let handler = api.handler;
+    let task = tokio::spawn(async move {
+        let _guard = match writes {
+            Some(writes) => Some(writes.lock_owned().await),
+            None => None,
+        };
+        let result = handler(cli, Output::captured(tx.clone())).await;
+        if let Err(ref e) = result {
+            let _ = tx.send(OutputEvent::Error { error: e.into() });
+        }
+        result.err().map(|e| error_status(&e))
+    });
+
+    if stream {
+        let events = futures::stream::unfold(rx, |mut rx| async move {
+            let event = rx.recv().await?;
+            Some((Ok::<_, Infallible>(Frame::data(sse_frame(&event))), rx))
+        });
+        return Response::builder()
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .body(StreamBody::new(events).boxed())
+            .expect("valid response");
+    }
+
+    let mut result = serde_json::Value::Null;
+    let mut events = Vec::new();
+    let mut error = None;
+    while let Some(event) = rx.recv().await {
+        match event {
+            OutputEvent::Result { data } => result = data,
+            OutputEvent::Error { error: info } => error = Some(info),
+            event => events.push(event),
+        }
+    }
+
+    let status = match task.await {
+        Ok(None) => StatusCode::OK,
+        Ok(Some(status)) => status,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let v = EVENT_SCHEMA_VERSION;
+    let body = match error {
+        Some(error) => serde_json::json!({ "v": v, "error": error, "events": events }),
+        None => serde_json::json!({ "v": v, "result": result, "events": events }),
+    };
+    json_response(status, &body)
+}
+
+/// Whether a command installs, starts, stops or removes apps
+fn changes_apps(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Install { .. }
+            | Commands::Run { .. }
+            | Commands::Stop { .. }
+            | Commands::Remove { .. }
+    )
+}
+
+fn error_status(error: &VulnPkgError) -> StatusCode {
+    match error {
+        VulnPkgError::AppNotFound(_) | VulnPkgError::AppNotInstalled(_) => StatusCode::NOT_FOUND,
+        VulnPkgError::AppAlreadyRunning(_) | VulnPkgError::AppNotRunning(_) => StatusCode::CONFLICT,
+        VulnPkgError::ManifestRejected => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn sse_frame(event: &OutputEvent) -> Bytes {
+    Bytes::from(format!(
+        "event: {}\ndata: {}\n\n",
+        event.kind(),
+        event.to_json_line()
+    ))
+}
+
+fn json_response(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())).boxed())
+        .expect("valid response")
+}
+
+/// Random bearer token for the TCP listener (128 bits from the OS RNG)
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| VulnPkgError::ApiServer(format!("Failed to generate a token: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        assert!(matches!(
+            route(&Method::GET, "/apps", None),
+            Ok(Commands::List { filter: None, .. })
+        ));
+        assert!(matches!(
+            route(&Method::GET, "/search", Some("q=sql+injection&sort=name")),
+            Ok(Commands::Search { query, sort: SortKey::Name }) if query.as_str() == "sql injection"
+        ));
+        assert_eq!(
+            route(&Method::GET, "/apps", Some("filter=type%3Azip")).err(),
+            Some(StatusCode::BAD_REQUEST)
+        );
+        assert!(matches!(
+            route(&Method::POST, "/apps/dvwa/run", Some("ttl=2h")),
+            Ok(Commands::Run { apps, ttl: Some(_), .. }) if apps == ["dvwa"]
+        ));
+        assert!(matches!(
+            route(&Method::POST, "/apps/dvwa/remove", Some("purge=true")),
+            Ok(Commands::Remove { purge: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_route_errors() {
+        assert_eq!(
+            route(&Method::POST, "/apps", None).err(),
+            Some(StatusCode::METHOD_NOT_ALLOWED)
+        );
+        assert_eq!(
+            route(&Method::POST, "/apps/dvwa/explode", None).err(),
+            Some(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(
+            route(&Method::POST, "/apps/dvwa/run", Some("ttl=soon")).err(),
+            Some(StatusCode::BAD_REQUEST)
+        );
+        // Patterns would select every matching app
+        for path in ["/apps/*/remove", "/apps/%2A/stop", "/apps/dvw%3F/run"] {
+            assert_eq!(
+                route(&Method::POST, path, None).err(),
+                Some(StatusCode::BAD_REQUEST),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token().unwrap());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("juice%2Dshop"), "juice-shop");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secrets"));
+    }
+}
@@ -1,21 +1,44 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::error::{Result, VulnPkgError};
-use crate::manifest::Protocol;
+use crate::manifest::{App, Protocol};
 
 const STATE_DIR: &str = ".vuln-pkg";
 const MANIFESTS_DIR: &str = "manifests";
 const IMAGES_DIR: &str = "images";
 const REPOS_DIR: &str = "repos";
+const DOWNLOADS_DIR: &str = "downloads";
 const STATE_FILE: &str = "state.json";
 const ACCEPTED_MANIFESTS_FILE: &str = "accepted-manifests.json";
+const CREDENTIALS_FILE: &str = "credentials.yml";
 
 /// Port allocation range for TCP/UDP direct mappings
 const PORT_RANGE_START: u16 = 40000;
 const PORT_RANGE_END: u16 = 49999;
 
+/// Current schema version of `state.json`
+pub const STATE_VERSION: u32 = 4;
+/// Current schema version of `accepted-manifests.json`
+pub const ACCEPTED_MANIFESTS_VERSION: u32 = 1;
+
+/// A single schema upgrade step, applied to the raw JSON document
+type Migration = fn(&mut Value);
+
+/// State migrations; entry `i` upgrades a document from version `i` to `i + 1`
+const STATE_MIGRATIONS: &[Migration] = &[
+    migrate_state_v0_to_v1,
+    migrate_state_v1_to_v2,
+    migrate_state_v2_to_v3,
+    migrate_state_v3_to_v4,
+];
+
+/// Accepted manifest migrations; entry `i` upgrades a document from version `i` to `i + 1`
+const ACCEPTED_MANIFESTS_MIGRATIONS: &[Migration] = &[migrate_accepted_manifests_v0_to_v1];
+
 /// Information about an accepted manifest
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AcceptedManifest {
@@ -36,11 +59,23 @@ pub struct AcceptedManifest {
 }
 
 /// Tracks accepted manifests by URL
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AcceptedManifests {
+    /// Schema version of the file this was loaded from
+    #[serde(default)]
+    pub version: u32,
     pub manifests: HashMap<String, AcceptedManifest>,
 }
 
+impl Default for AcceptedManifests {
+    fn default() -> Self {
+        Self {
+            version: ACCEPTED_MANIFESTS_VERSION,
+            manifests: HashMap::new(),
+        }
+    }
+}
+
 /// Tracks how the Docker image was obtained
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -52,6 +87,8 @@ pub enum ImageSource {
     Dockerfile,
     /// Built from a git repository
     Git,
+    /// Built from a local directory
+    Local,
 }
 
 /// Represents an allocated port mapping for TCP/UDP protocols
@@ -70,10 +107,11 @@ pub struct AllocatedPort {
 
 /// Endpoint information for display
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)]
 pub struct Endpoint {
     /// The URL or connection string
     pub url: String,
+    /// Container port this endpoint reaches
+    pub port: u16,
     /// Protocol type
     pub protocol: Protocol,
     /// Optional label
@@ -108,18 +146,71 @@ pub struct AppState {
     /// Allocated ports for TCP/UDP direct mappings
     #[serde(default)]
     pub allocated_ports: Vec<AllocatedPort>,
+
+    /// Whether the app was started with --https (its hostnames are served over HTTPS)
+    #[serde(default)]
+    pub https: bool,
+
+    /// When `vuln-pkg gc` should remove the app (ISO 8601 format, set by `run --ttl`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+impl AppState {
+    /// Endpoint selected by label (case-insensitive) or container port
+    pub fn find_endpoint<'a>(endpoints: &'a [Endpoint], port: &str) -> Option<&'a Endpoint> {
+        endpoints.iter().find(|e| {
+            e.port.to_string() == port
+                || e.label
+                    .as_deref()
+                    .is_some_and(|l| l.eq_ignore_ascii_case(port))
+        })
+    }
+
+    /// Current endpoints: a URL per HTTP port (in manifest order, matching
+    /// `hostnames`) and `host:port` for each allocated TCP/UDP port
+    pub fn endpoints(&self, app: &App, host: &str) -> Vec<Endpoint> {
+        let scheme = if self.https { "https" } else { "http" };
+        let http = app
+            .http_ports()
+            .into_iter()
+            .zip(&self.hostnames)
+            .map(|(port, hostname)| Endpoint {
+                url: format!("{}://{}", scheme, hostname),
+                port: port.port,
+                protocol: Protocol::Http,
+                label: port.label,
+            });
+        let direct = self.allocated_ports.iter().map(|alloc| Endpoint {
+            url: format!("{}:{}", host, alloc.host_port),
+            port: alloc.container_port,
+            protocol: alloc.protocol.clone(),
+            label: alloc.label.clone(),
+        });
+        http.chain(direct).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
+    /// Schema version of the file this was loaded from
+    #[serde(default)]
+    pub version: u32,
     pub apps: HashMap<String, AppState>,
     pub network_id: Option<String>,
     pub traefik_container_id: Option<String>,
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl State {
     pub fn new() -> Self {
         Self {
+            version: STATE_VERSION,
             apps: HashMap::new(),
             network_id: None,
             traefik_container_id: None,
@@ -163,6 +254,15 @@ pub struct StateManager {
     base_dir: PathBuf,
 }
 
+/// What `cache clean` removed from one cache directory
+#[derive(Debug, Serialize)]
+pub struct CleanedCache {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
 impl StateManager {
     pub fn new() -> Result<Self> {
         let home = dirs_home()
@@ -175,6 +275,7 @@ impl StateManager {
         std::fs::create_dir_all(self.manifests_dir())?;
         std::fs::create_dir_all(self.images_dir())?;
         std::fs::create_dir_all(self.repos_dir())?;
+        std::fs::create_dir_all(self.downloads_dir())?;
 
         // Create state file if it doesn't exist
         if !self.state_file().exists() {
@@ -201,6 +302,16 @@ impl StateManager {
         self.base_dir.join(REPOS_DIR)
     }
 
+    /// Checksum-verified Dockerfiles and build contexts, named by their SHA-256
+    pub fn downloads_dir(&self) -> PathBuf {
+        self.base_dir.join(DOWNLOADS_DIR)
+    }
+
+    /// Per-host git credential settings (written by the user, never by vuln-pkg)
+    pub fn credentials_file(&self) -> PathBuf {
+        self.base_dir.join(CREDENTIALS_FILE)
+    }
+
     pub fn state_file(&self) -> PathBuf {
         self.base_dir.join(STATE_FILE)
     }
@@ -210,12 +321,12 @@ impl StateManager {
             return Ok(State::new());
         }
         let content = std::fs::read_to_string(self.state_file())?;
-        let state: State = serde_json::from_str(&content)
-            .map_err(|e| VulnPkgError::State(format!("Failed to parse state: {}", e)))?;
-        Ok(state)
+        load_versioned(&content, STATE_FILE, STATE_MIGRATIONS)
     }
 
+    /// Save state, refusing to overwrite a file written by a newer schema
     pub fn save_state(&self, state: &State) -> Result<()> {
+        ensure_writable(&self.state_file(), STATE_FILE, state.version, STATE_VERSION)?;
         let content = serde_json::to_string_pretty(state)
             .map_err(|e| VulnPkgError::State(format!("Failed to serialize state: {}", e)))?;
         std::fs::write(self.state_file(), content)?;
@@ -229,6 +340,21 @@ impl StateManager {
         Ok(path)
     }
 
+    /// Delete all git clones (they are re-cloned on the next build)
+    pub fn clean_repos(&self) -> Result<CleanedCache> {
+        clear_dir("repos", self.repos_dir())
+    }
+
+    /// Delete all cached manifest copies; acceptance records are kept
+    pub fn clean_manifests(&self) -> Result<CleanedCache> {
+        clear_dir("manifests", self.manifests_dir())
+    }
+
+    /// Delete all verified downloads (fetched again on the next build)
+    pub fn clean_downloads(&self) -> Result<CleanedCache> {
+        clear_dir("downloads", self.downloads_dir())
+    }
+
     #[allow(dead_code)]
     pub fn get_cached_manifest(&self, url: &str) -> Option<PathBuf> {
         let filename = url_to_filename(url);
@@ -245,13 +371,21 @@ impl StateManager {
             return Ok(AcceptedManifests::default());
         }
         let content = std::fs::read_to_string(self.accepted_manifests_file())?;
-        let accepted: AcceptedManifests = serde_json::from_str(&content).map_err(|e| {
-            VulnPkgError::State(format!("Failed to parse accepted manifests: {}", e))
-        })?;
-        Ok(accepted)
+        load_versioned(
+            &content,
+            ACCEPTED_MANIFESTS_FILE,
+            ACCEPTED_MANIFESTS_MIGRATIONS,
+        )
     }
 
+    /// Save accepted manifests, refusing to overwrite a file written by a newer schema
     pub fn save_accepted_manifests(&self, accepted: &AcceptedManifests) -> Result<()> {
+        ensure_writable(
+            &self.accepted_manifests_file(),
+            ACCEPTED_MANIFESTS_FILE,
+            accepted.version,
+            ACCEPTED_MANIFESTS_VERSION,
+        )?;
         let content = serde_json::to_string_pretty(accepted).map_err(|e| {
             VulnPkgError::State(format!("Failed to serialize accepted manifests: {}", e))
         })?;
@@ -297,10 +431,177 @@ fn dirs_home() -> Option<PathBuf> {
     directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf())
 }
 
+/// Empty `dir`, keeping the directory itself
+fn clear_dir(name: &'static str, dir: PathBuf) -> Result<CleanedCache> {
+    let mut cleaned = CleanedCache {
+        name,
+        path: dir,
+        entries: 0,
+        bytes: 0,
+    };
+    let entries = match std::fs::read_dir(&cleaned.path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(cleaned),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        cleaned.bytes += disk_usage(&path);
+        if path.is_dir() && !path.is_symlink() {
+            std::fs::remove_dir_all(&path)?;
+        } else {
+            std::fs::remove_file(&path)?;
+        }
+        cleaned.entries += 1;
+    }
+    Ok(cleaned)
+}
+
+/// Total size of the files under `path`, without following symlinks
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
 fn url_to_filename(url: &str) -> String {
     url.replace(['/', ':', '.'], "_") + ".yml"
 }
 
+/// Read the schema version of a JSON document (files without one are version 0)
+fn document_version(doc: &Value) -> u32 {
+    doc.get("version")
+        .and_then(Value::as_u64)
+        .map(|v| v as u32)
+        .unwrap_or(0)
+}
+
+/// Parse a versioned JSON document, running any migrations needed to bring it
+/// up to the current schema.
+///
+/// Documents from a newer schema are parsed as-is so read-only commands keep
+/// working; their version is preserved so a later save is refused.
+fn load_versioned<T: DeserializeOwned>(
+    content: &str,
+    file: &str,
+    migrations: &[Migration],
+) -> Result<T> {
+    let doc: Value = serde_json::from_str(content)
+        .map_err(|e| VulnPkgError::State(format!("Failed to parse {}: {}", file, e)))?;
+    let supported = migrations.len() as u32;
+    let found = document_version(&doc);
+
+    if found > supported {
+        return serde_json::from_value(doc).map_err(|_| VulnPkgError::StateVersion {
+            file: file.to_string(),
+            found,
+            supported,
+        });
+    }
+
+    let doc = migrate(doc, migrations)?;
+    serde_json::from_value(doc)
+        .map_err(|e| VulnPkgError::State(format!("Failed to parse {}: {}", file, e)))
+}
+
+/// Apply every migration from the document's version up to the current one
+fn migrate(mut doc: Value, migrations: &[Migration]) -> Result<Value> {
+    if !doc.is_object() {
+        return Err(VulnPkgError::State(
+            "Expected a JSON object at the top level".to_string(),
+        ));
+    }
+
+    let from = document_version(&doc) as usize;
+    for (step, migration) in migrations.iter().enumerate().skip(from) {
+        migration(&mut doc);
+        doc["version"] = Value::from(step as u32 + 1);
+    }
+    Ok(doc)
+}
+
+/// Refuse to write a file when either the in-memory copy or the file on disk
+/// comes from a newer schema than this binary understands
+fn ensure_writable(path: &Path, file: &str, loaded: u32, supported: u32) -> Result<()> {
+    let on_disk = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .map(|doc| document_version(&doc))
+        .unwrap_or(0);
+
+    let found = loaded.max(on_disk);
+    if found > supported {
+        return Err(VulnPkgError::StateVersion {
+            file: file.to_string(),
+            found,
+            supported,
+        });
+    }
+    Ok(())
+}
+
+/// v0 -> v1: introduce the `version` field and backfill fields that older
+/// releases could omit
+fn migrate_state_v0_to_v1(doc: &mut Value) {
+    let Some(obj) = doc.as_object_mut() else {
+        return;
+    };
+    obj.entry("apps")
+        .or_insert_with(|| Value::Object(Default::default()));
+    obj.entry("network_id").or_insert(Value::Null);
+    obj.entry("traefik_container_id").or_insert(Value::Null);
+
+    if let Some(apps) = obj.get_mut("apps").and_then(Value::as_object_mut) {
+        for app in apps.values_mut().filter_map(Value::as_object_mut) {
+            app.entry("installed").or_insert(Value::Bool(false));
+            app.entry("running").or_insert(Value::Bool(false));
+            app.entry("container_id").or_insert(Value::Null);
+            app.entry("hostnames")
+                .or_insert_with(|| Value::Array(Vec::new()));
+        }
+    }
+}
+
+/// v1 -> v2: apps may carry `expires_at`. Absent means no TTL, so there is
+/// nothing to backfill; the bump stops v1 binaries from saving TTLs away.
+fn migrate_state_v1_to_v2(_doc: &mut Value) {}
+
+/// v2 -> v3: apps record whether they were started with `--https`. Older
+/// releases could only have served them over HTTP.
+fn migrate_state_v2_to_v3(doc: &mut Value) {
+    let apps = doc.get_mut("apps").and_then(Value::as_object_mut);
+    for app in apps.into_iter().flat_map(|apps| apps.values_mut()) {
+        if let Some(app) = app.as_object_mut() {
+            app.entry("https").or_insert(Value::Bool(false));
+        }
+    }
+}
+
+/// v3 -> v4: `image_source` may be `local`. Existing values stay valid; the
+/// bump makes older binaries report a version error instead of failing to
+/// parse the new value.
+fn migrate_state_v3_to_v4(_doc: &mut Value) {}
+
+/// v0 -> v1: introduce the `version` field
+fn migrate_accepted_manifests_v0_to_v1(doc: &mut Value) {
+    if let Some(obj) = doc.as_object_mut() {
+        obj.entry("manifests")
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -312,4 +613,130 @@ mod tests {
             "https___vulns_io_apps_yml.yml"
         );
     }
+
+    #[test]
+    fn test_migration_tables_match_versions() {
+        assert_eq!(STATE_MIGRATIONS.len() as u32, STATE_VERSION);
+        assert_eq!(
+            ACCEPTED_MANIFESTS_MIGRATIONS.len() as u32,
+            ACCEPTED_MANIFESTS_VERSION
+        );
+    }
+
+    #[test]
+    fn test_migrate_unversioned_state() {
+        let legacy = r#"{
+            "apps": {
+                "dvwa": { "installed": true, "running": false, "container_id": null }
+            },
+            "network_id": null
+        }"#;
+        let state: State = load_versioned(legacy, STATE_FILE, STATE_MIGRATIONS).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert!(state.apps["dvwa"].installed);
+        assert!(state.apps["dvwa"].hostnames.is_empty());
+        assert!(state.traefik_container_id.is_none());
+    }
+
+    #[test]
+    fn test_migrate_v2_state_backfills_https() {
+        let v2 = r#"{
+            "version": 2,
+            "apps": { "dvwa": { "installed": true, "running": true, "container_id": "abc", "hostnames": [] } },
+            "network_id": null,
+            "traefik_container_id": null
+        }"#;
+        let doc = migrate(serde_json::from_str(v2).unwrap(), STATE_MIGRATIONS).unwrap();
+        assert_eq!(doc["version"], STATE_VERSION);
+        assert_eq!(doc["apps"]["dvwa"]["https"], false);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_accepted_manifests() {
+        let legacy = r#"{ "manifests": {} }"#;
+        let accepted: AcceptedManifests = load_versioned(
+            legacy,
+            ACCEPTED_MANIFESTS_FILE,
+            ACCEPTED_MANIFESTS_MIGRATIONS,
+        )
+        .unwrap();
+        assert_eq!(accepted.version, ACCEPTED_MANIFESTS_VERSION);
+    }
+
+    #[test]
+    fn test_newer_state_is_readable_but_not_writable() {
+        let newer = format!(
+            r#"{{ "version": {}, "apps": {{}}, "network_id": null, "traefik_container_id": null, "future": true }}"#,
+            STATE_VERSION + 1
+        );
+        let state: State = load_versioned(&newer, STATE_FILE, STATE_MIGRATIONS).unwrap();
+        assert_eq!(state.version, STATE_VERSION + 1);
+
+        let path = std::env::temp_dir().join("vuln-pkg-test-missing-state.json");
+        let err = ensure_writable(&path, STATE_FILE, state.version, STATE_VERSION).unwrap_err();
+        assert!(matches!(err, VulnPkgError::StateVersion { .. }));
+        assert!(err.to_string().contains("newer vuln-pkg"));
+    }
+
+    #[test]
+    fn test_newer_unparseable_state_reports_version() {
+        let newer = format!(r#"{{ "version": {}, "apps": [] }}"#, STATE_VERSION + 1);
+        let err = load_versioned::<State>(&newer, STATE_FILE, STATE_MIGRATIONS).unwrap_err();
+        assert!(matches!(err, VulnPkgError::StateVersion { .. }));
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let app = crate::manifest::Manifest::parse(
+            r#"
+apps:
+  - name: lab
+    version: "1.0"
+    image: example/lab
+    ports:
+      - port: 80
+        label: Web
+      - 8080
+      - port: 3306
+        protocol: tcp
+        label: MySQL
+"#,
+        )
+        .unwrap()
+        .apps
+        .remove(0);
+
+        let app_state = AppState {
+            hostnames: vec![
+                "lab.lab.local".to_string(),
+                "lab-8080.lab.local".to_string(),
+            ],
+            allocated_ports: vec![AllocatedPort {
+                container_port: 3306,
+                host_port: 40001,
+                protocol: Protocol::Tcp,
+                label: Some("MySQL".to_string()),
+            }],
+            https: true,
+            ..Default::default()
+        };
+
+        let endpoints = app_state.endpoints(&app, "10.0.0.5");
+        let urls: Vec<&str> = endpoints.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://lab.lab.local",
+                "https://lab-8080.lab.local",
+                "10.0.0.5:40001"
+            ]
+        );
+        assert_eq!(endpoints[0].label.as_deref(), Some("Web"));
+        assert_eq!(endpoints[2].protocol, Protocol::Tcp);
+
+        let find = |port: &str| AppState::find_endpoint(&endpoints, port).map(|e| e.url.as_str());
+        assert_eq!(find("mysql"), Some("10.0.0.5:40001"));
+        assert_eq!(find("8080"), Some("https://lab-8080.lab.local"));
+        assert_eq!(find("ssh"), None);
+    }
 }
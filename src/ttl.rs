@@ -0,0 +1,182 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::state::State;
+
+/// Longest lifetime `parse_ttl` accepts
+const MAX_TTL_DAYS: i64 = 365;
+
+/// Parse a lifetime such as `90m`, `2h`, `1h30m` or `1d` (a bare number is
+/// minutes), up to 365 days
+pub fn parse_ttl(input: &str) -> Result<TimeDelta, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("TTL must not be empty".to_string());
+    }
+
+    let mut total = TimeDelta::zero();
+    let mut digits = String::new();
+    if let Ok(minutes) = input.parse::<i64>() {
+        total = checked(minutes, 60, input)?;
+    } else {
+        for c in input.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+
+            let unit = match c.to_ascii_lowercase() {
+                's' => 1,
+                'm' => 60,
+                'h' => 60 * 60,
+                'd' => 24 * 60 * 60,
+                _ => return Err(format!("invalid TTL '{}': unknown unit '{}'", input, c)),
+            };
+            let value: i64 = digits.parse().map_err(|_| {
+                format!("invalid TTL '{}': expected a number before '{}'", input, c)
+            })?;
+            total = total
+                .checked_add(&checked(value, unit, input)?)
+                .ok_or_else(|| format!("invalid TTL '{}'", input))?;
+            digits.clear();
+        }
+    }
+
+    if !digits.is_empty() {
+        return Err(format!(
+            "invalid TTL '{}': missing unit after {}",
+            input, digits
+        ));
+    }
+    if total <= TimeDelta::zero() {
+        return Err(format!("invalid TTL '{}': must be positive", input));
+    }
+    if total > TimeDelta::days(MAX_TTL_DAYS) {
+        return Err(format!(
+            "invalid TTL '{}': must be at most {}d",
+            input, MAX_TTL_DAYS
+        ));
+    }
+    Ok(total)
+}
+
+fn checked(value: i64, unit: i64, input: &str) -> Result<TimeDelta, String> {
+    value
+        .checked_mul(unit)
+        .and_then(TimeDelta::try_seconds)
+        .ok_or_else(|| format!("invalid TTL '{}'", input))
+}
+
+/// Parse an `expires_at` timestamp from state
+pub fn parse_expiry(expires_at: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(expires_at)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Apps whose TTL has run out, sorted by name
+pub fn expired_apps(state: &State, now: DateTime<Utc>) -> Vec<String> {
+    let mut expired: Vec<String> = state
+        .apps
+        .iter()
+        .filter(|(_, app)| {
+            app.expires_at
+                .as_deref()
+                .and_then(parse_expiry)
+                .is_some_and(|at| at <= now)
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    expired.sort();
+    expired
+}
+
+/// Human-readable time left, e.g. `1h 05m`, or `expired`
+pub fn format_remaining(remaining: TimeDelta) -> String {
+    if remaining <= TimeDelta::zero() {
+        return "expired".to_string();
+    }
+
+    let total = remaining.num_seconds();
+    let (days, hours, minutes, seconds) = (
+        total / 86_400,
+        total % 86_400 / 3600,
+        total % 3600 / 60,
+        total % 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("2h").unwrap(), TimeDelta::hours(2));
+        assert_eq!(parse_ttl("1h30m").unwrap(), TimeDelta::minutes(90));
+        assert_eq!(parse_ttl("45s").unwrap(), TimeDelta::seconds(45));
+        assert_eq!(parse_ttl("1d").unwrap(), TimeDelta::days(1));
+        assert_eq!(parse_ttl("30").unwrap(), TimeDelta::minutes(30));
+        // Only the total has to be positive
+        assert_eq!(parse_ttl("1h0m").unwrap(), TimeDelta::hours(1));
+    }
+
+    #[test]
+    fn test_parse_ttl_rejects_garbage() {
+        assert!(parse_ttl("").is_err());
+        assert!(parse_ttl("0").is_err());
+        assert!(parse_ttl("0h").is_err());
+        assert!(parse_ttl("0h0m").is_err());
+        assert!(parse_ttl("-5").is_err());
+        assert!(parse_ttl("2x").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("1h30").is_err());
+    }
+
+    #[test]
+    fn test_parse_ttl_upper_bound() {
+        assert_eq!(parse_ttl("365d").unwrap(), TimeDelta::days(365));
+        assert!(parse_ttl("366d").is_err());
+        assert!(parse_ttl("365d1s").is_err());
+        assert!(parse_ttl("9999999999999").is_err());
+    }
+
+    #[test]
+    fn test_expired_apps() {
+        let now = Utc::now();
+        let mut state = State::new();
+        for (name, expires_at) in [
+            ("past", Some(now - TimeDelta::minutes(1))),
+            ("future", Some(now + TimeDelta::minutes(1))),
+            ("forever", None),
+        ] {
+            state.apps.insert(
+                name.to_string(),
+                AppState {
+                    expires_at: expires_at.map(|t| t.to_rfc3339()),
+                    ..Default::default()
+                },
+            );
+        }
+
+        assert_eq!(expired_apps(&state, now), vec!["past"]);
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(TimeDelta::seconds(-5)), "expired");
+        assert_eq!(format_remaining(TimeDelta::seconds(42)), "42s");
+        assert_eq!(format_remaining(TimeDelta::seconds(125)), "2m 05s");
+        assert_eq!(format_remaining(TimeDelta::minutes(65)), "1h 05m");
+        assert_eq!(format_remaining(TimeDelta::hours(50)), "2d 2h");
+    }
+}